@@ -0,0 +1,58 @@
+//! Compares converting a frame through `ValueToColor` with the precomputed
+//! `ColorLut`. Run with `cargo bench`. The crate has no library target, so
+//! the modules are included by path.
+
+#[allow(dead_code)]
+#[path = "../src/color_maps.rs"]
+mod color_maps;
+#[allow(dead_code)]
+#[path = "../src/color_map_listed.rs"]
+mod color_map_listed;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use macroquad::color::Color;
+use color_maps::{ColorLut, ListedColorMap, ValueToColor};
+use color_map_listed::INFERNO_LUT;
+
+const W: usize = 640;
+const H: usize = 360;
+const ITERATIONS: u32 = 200;
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    // warm up caches before measuring
+    for _ in 0..10 {
+        f();
+    }
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let per_frame: Duration = start.elapsed() / ITERATIONS;
+    println!("{:<24}{:>10.3} ms/frame", name, per_frame.as_secs_f64() * 1000.0);
+}
+
+fn main() {
+    let heat: Vec<u8> = (0..W * H).map(|i| ((i * 7 + i / W) % 256) as u8).collect();
+    let obstacles: Vec<bool> = (0..W * H).map(|i| i % 97 == 0).collect();
+    let obstacle_color = Color::from_rgba(70, 90, 110, 255);
+    let color_map: Box<dyn ValueToColor> = Box::new(ListedColorMap::new(INFERNO_LUT));
+
+    bench("value_to_color", || {
+        let colors: Vec<Color> = heat
+            .iter()
+            .zip(&obstacles)
+            .map(|(&val, &solid)| if solid { obstacle_color } else { color_map.value_to_color(val, None) })
+            .collect();
+        // Image::update converts every color back to bytes
+        let pixels: Vec<[u8; 4]> = colors.iter().map(|&c| c.into()).collect();
+        black_box(pixels);
+    });
+
+    let lut = ColorLut::new(color_map.as_ref());
+    let mut pixels = vec![[0u8; 4]; W * H];
+    bench("color_lut", || {
+        lut.apply_masked(black_box(&heat), &obstacles, [70, 90, 110, 255], &mut pixels);
+        black_box(&pixels);
+    });
+}
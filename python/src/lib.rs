@@ -0,0 +1,360 @@
+use numpy::ndarray::{Array2, Array3};
+use numpy::{IntoPyArray, PyArray2, PyArray3, PyReadonlyArray2};
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyString};
+
+// The simulation is shared with the application by including its modules by
+// path, the same way the benches do.
+#[allow(dead_code)]
+#[path = "../../src/color_map_listed.rs"]
+mod color_map_listed;
+#[allow(dead_code)]
+#[path = "../../src/color_maps.rs"]
+mod color_maps;
+#[allow(dead_code)]
+#[path = "../../src/configs.rs"]
+mod configs;
+#[allow(dead_code)]
+#[path = "../../src/cooling_maps.rs"]
+mod cooling_maps;
+#[allow(dead_code)]
+#[path = "../../src/defaults.rs"]
+mod defaults;
+#[allow(dead_code)]
+#[path = "../../src/fire_handler.rs"]
+mod fire_handler;
+#[allow(dead_code)]
+#[path = "../../src/fire_masks.rs"]
+mod fire_masks;
+#[allow(dead_code)]
+#[path = "../../src/fluid.rs"]
+mod fluid;
+#[allow(dead_code)]
+#[path = "../../src/forces.rs"]
+mod forces;
+#[allow(dead_code)]
+#[path = "../../src/fuel.rs"]
+mod fuel;
+#[allow(dead_code)]
+#[path = "../../src/rng.rs"]
+mod rng;
+#[allow(dead_code)]
+#[path = "../../src/simulation.rs"]
+mod simulation;
+#[allow(dead_code)]
+#[path = "../../src/snapshot.rs"]
+mod snapshot;
+#[allow(dead_code)]
+#[path = "../../src/stats.rs"]
+mod stats;
+
+use color_maps::ColorLut;
+use configs::FireConfigs;
+use defaults::{DEFAULT_PRE_ROLL_MAX_FRAMES, DEFAULT_PRE_ROLL_TOLERANCE, DEFAULT_PRE_ROLL_WINDOW};
+use simulation::{FireSimulation, PreRoll};
+
+const COLOR_MAP_NAMES: [&str; 5] = ["gray", "magma", "inferno", "plasma", "viridis"];
+
+/// Converts a Python value to the text form the configs are set from.
+fn config_text(value: &Bound<'_, PyAny>) -> PyResult<String> {
+    if value.is_none() {
+        Ok(String::from("none"))
+    } else if value.is_instance_of::<PyBool>() {
+        Ok(value.extract::<bool>()?.to_string())
+    } else if value.is_instance_of::<PyInt>() {
+        Ok(value.extract::<i64>()?.to_string())
+    } else if value.is_instance_of::<PyFloat>() {
+        // whole floats print without a fraction, so they also set integer configs
+        Ok(value.extract::<f64>()?.to_string())
+    } else if value.is_instance_of::<PyString>() {
+        value.extract()
+    } else {
+        Err(PyTypeError::new_err(format!("unsupported config value {}", value.repr()?)))
+    }
+}
+
+/// Sets all entries of `values` on `fire_configs`, leaving it untouched if
+/// any of them is invalid.
+fn update_configs(fire_configs: &mut FireConfigs, values: &Bound<'_, PyDict>) -> PyResult<()> {
+    let mut updated = fire_configs.clone();
+    for (key, value) in values.iter() {
+        let key: String = key.extract()?;
+        updated.set(&key, &config_text(&value)?).map_err(PyValueError::new_err)?;
+    }
+    *fire_configs = updated;
+    Ok(())
+}
+
+/// Table of the color map given as a name, as a (256, 3) uint8 array or as a
+/// (256, 3) or (256, 4) float array with values in 0..1, such as
+/// `matplotlib.cm.magma(np.linspace(0, 1, 256))`. Alpha is ignored.
+fn color_lut(color_map: &Bound<'_, PyAny>) -> PyResult<ColorLut> {
+    if let Ok(name) = color_map.extract::<String>() {
+        if !COLOR_MAP_NAMES.contains(&name.to_ascii_lowercase().as_str()) {
+            return Err(PyValueError::new_err(format!(
+                "unknown color map {}, expected one of {}",
+                name,
+                COLOR_MAP_NAMES.join(", ")
+            )));
+        }
+        return Ok(ColorLut::named(&name));
+    }
+    let mut lut = [[0u8; 3]; 256];
+    if let Ok(array) = color_map.extract::<PyReadonlyArray2<u8>>() {
+        let array = array.as_array();
+        check_table_shape(array.dim())?;
+        for (entry, row) in lut.iter_mut().zip(array.rows()) {
+            *entry = [row[0], row[1], row[2]];
+        }
+    } else if let Ok(array) = color_map.extract::<PyReadonlyArray2<f64>>() {
+        let array = array.as_array();
+        check_table_shape(array.dim())?;
+        let to_u8 = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        for (entry, row) in lut.iter_mut().zip(array.rows()) {
+            *entry = [to_u8(row[0]), to_u8(row[1]), to_u8(row[2])];
+        }
+    } else {
+        return Err(PyTypeError::new_err(
+            "expected a color map name or a (256, 3) uint8 or float64 array",
+        ));
+    }
+    Ok(ColorLut::new(&color_maps::ListedColorMap::new(lut)))
+}
+
+fn check_table_shape((rows, columns): (usize, usize)) -> PyResult<()> {
+    if rows != 256 || !(3..=4).contains(&columns) {
+        return Err(PyValueError::new_err(format!(
+            "expected a color map of shape (256, 3) or (256, 4), got ({}, {})",
+            rows, columns
+        )));
+    }
+    Ok(())
+}
+
+/// Colors `heat` of size `w` x `h` into an (h, w, 3) RGB array, `mask_color`
+/// where `mask` is set.
+fn rgb_array<'py>(
+    py: Python<'py>,
+    color_lut: &ColorLut,
+    heat: &[u8],
+    mask: &[bool],
+    mask_color: [u8; 4],
+    w: usize,
+    h: usize,
+) -> Bound<'py, PyArray3<u8>> {
+    let mut pixels = vec![[0u8; 4]; w * h];
+    color_lut.apply_masked(heat, mask, mask_color, &mut pixels);
+    let rgb = pixels.iter().flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect();
+    Array3::from_shape_vec((h, w, 3), rgb).unwrap().into_pyarray(py)
+}
+
+/// The 2D fire simulation together with the configs that drive it.
+#[pyclass(name = "Simulation", module = "rusty_fire")]
+struct PySimulation {
+    simulation: FireSimulation,
+    fire_configs: FireConfigs,
+}
+
+#[pymethods]
+impl PySimulation {
+    /// Creates a simulation of `width` x `height` cells. `configs` maps config
+    /// keys such as "cooling.strength" to values, everything else keeps its
+    /// default.
+    #[new]
+    #[pyo3(signature = (width, height, configs=None))]
+    fn new(width: usize, height: usize, configs: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        if width == 0 || height == 0 {
+            return Err(PyValueError::new_err("width and height must be positive"));
+        }
+        if width.checked_mul(height).is_none_or(|cells| cells > snapshot::MAX_CELLS) {
+            return Err(PyValueError::new_err(format!(
+                "{} x {} cells are more than the {} a simulation may have",
+                width,
+                height,
+                snapshot::MAX_CELLS
+            )));
+        }
+        let mut fire_configs = FireConfigs::default();
+        if let Some(configs) = configs {
+            update_configs(&mut fire_configs, configs)?;
+        }
+        let simulation = FireSimulation::new(&fire_configs, width, height);
+        Ok(Self { simulation, fire_configs })
+    }
+
+    /// Restores a simulation from a snapshot saved by the application or by
+    /// `save_snapshot`.
+    #[staticmethod]
+    fn load_snapshot(path: &str) -> PyResult<Self> {
+        let (simulation, fire_configs) = snapshot::load_snapshot(path).map_err(PyValueError::new_err)?;
+        Ok(Self { simulation, fire_configs })
+    }
+
+    /// Saves the complete state, so that the application can continue it.
+    fn save_snapshot(&self, path: &str) -> PyResult<()> {
+        snapshot::save_snapshot(path, &self.simulation, &self.fire_configs).map_err(PyValueError::new_err)
+    }
+
+    /// Takes `steps` simulation steps.
+    #[pyo3(signature = (steps=1))]
+    fn step(&mut self, py: Python<'_>, steps: u64) {
+        let Self { simulation, fire_configs } = self;
+        py.detach(|| {
+            for _ in 0..steps {
+                simulation.step(fire_configs);
+            }
+        });
+    }
+
+    /// Runs the simulation ahead for `frames` steps, or until the mean heat
+    /// settles if not given. Returns the number of steps taken.
+    #[pyo3(signature = (frames=None))]
+    fn pre_roll(&mut self, py: Python<'_>, frames: Option<u64>) -> u64 {
+        let pre_roll = match frames {
+            Some(frames) => PreRoll::Frames(frames),
+            None => PreRoll::Converge {
+                window: DEFAULT_PRE_ROLL_WINDOW,
+                tolerance: DEFAULT_PRE_ROLL_TOLERANCE,
+                max_frames: DEFAULT_PRE_ROLL_MAX_FRAMES,
+            },
+        };
+        let Self { simulation, fire_configs } = self;
+        py.detach(|| simulation.pre_roll(fire_configs, pre_roll))
+    }
+
+    /// The heat of every cell as an (height, width) uint8 array.
+    fn heat<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<u8>> {
+        let simulation = &self.simulation;
+        Array2::from_shape_vec((simulation.h, simulation.w), simulation.buf.clone()).unwrap().into_pyarray(py)
+    }
+
+    /// The frame as an (height, width, 3) uint8 RGB array, colored with
+    /// `color_map` or the configured one. Obstacles get the obstacle color.
+    #[pyo3(signature = (color_map=None))]
+    fn rgb<'py>(&self, py: Python<'py>, color_map: Option<&Bound<'py, PyAny>>) -> PyResult<Bound<'py, PyArray3<u8>>> {
+        let color_lut = match color_map {
+            Some(color_map) => color_lut(color_map)?,
+            None => ColorLut::named(&self.fire_configs.color_map_name),
+        };
+        let simulation = &self.simulation;
+        let [r, g, b] = self.fire_configs.obstacle_configs.color;
+        Ok(rgb_array(py, &color_lut, &simulation.buf, &simulation.obstacles, [r, g, b, 255], simulation.w, simulation.h))
+    }
+
+    /// The cells blocked by obstacles as an (height, width) bool array.
+    #[getter]
+    fn obstacles<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<bool>> {
+        let simulation = &self.simulation;
+        Array2::from_shape_vec((simulation.h, simulation.w), simulation.obstacles.clone()).unwrap().into_pyarray(py)
+    }
+
+    #[setter]
+    fn set_obstacles(&mut self, obstacles: PyReadonlyArray2<bool>) -> PyResult<()> {
+        let obstacles = obstacles.as_array();
+        let simulation = &mut self.simulation;
+        if obstacles.dim() != (simulation.h, simulation.w) {
+            return Err(PyValueError::new_err(format!(
+                "expected obstacles of shape ({}, {}), got {:?}",
+                simulation.h,
+                simulation.w,
+                obstacles.shape()
+            )));
+        }
+        simulation.obstacles = obstacles.iter().copied().collect();
+        Ok(())
+    }
+
+    /// All configs as a dict of their text values.
+    #[getter]
+    fn configs<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let configs = PyDict::new(py);
+        for (key, value) in self.fire_configs.entries() {
+            configs.set_item(key, value)?;
+        }
+        Ok(configs)
+    }
+
+    /// Sets the config `key` to `value`.
+    fn set(&mut self, key: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.fire_configs.set(key, &config_text(value)?).map_err(PyValueError::new_err)
+    }
+
+    /// Sets all configs of `configs`, none of them if any is invalid.
+    fn update(&mut self, configs: &Bound<'_, PyDict>) -> PyResult<()> {
+        update_configs(&mut self.fire_configs, configs)
+    }
+
+    #[getter]
+    fn width(&self) -> usize {
+        self.simulation.w
+    }
+
+    #[getter]
+    fn height(&self) -> usize {
+        self.simulation.h
+    }
+
+    #[getter]
+    fn seed(&self) -> u64 {
+        self.simulation.seed
+    }
+
+    /// Number of steps taken so far.
+    #[getter]
+    fn frame(&self) -> u64 {
+        self.simulation.frame
+    }
+
+    fn mean_heat(&self) -> f64 {
+        self.simulation.mean_heat()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Simulation(width={}, height={}, seed={}, frame={})",
+            self.simulation.w, self.simulation.h, self.simulation.seed, self.simulation.frame
+        )
+    }
+}
+
+/// Names of the built-in color maps.
+#[pyfunction]
+fn color_map_names() -> Vec<&'static str> {
+    COLOR_MAP_NAMES.to_vec()
+}
+
+/// The built-in color map `name` as a (256, 3) uint8 array.
+#[pyfunction]
+fn color_map<'py>(py: Python<'py>, name: &Bound<'py, PyString>) -> PyResult<Bound<'py, PyArray2<u8>>> {
+    let color_lut = color_lut(name)?;
+    let table = (0..=255).flat_map(|value| {
+        let [r, g, b, _] = color_lut.color(value);
+        [r, g, b]
+    });
+    Ok(Array2::from_shape_vec((256, 3), table.collect()).unwrap().into_pyarray(py))
+}
+
+/// Colors a (height, width) uint8 heat array into an (height, width, 3) RGB
+/// array with `color_map`, a name or a table as taken by `Simulation.rgb`.
+#[pyfunction]
+fn apply_color_map<'py>(
+    py: Python<'py>,
+    heat: PyReadonlyArray2<u8>,
+    color_map: &Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyArray3<u8>>> {
+    let color_lut = color_lut(color_map)?;
+    let heat = heat.as_array();
+    let (h, w) = heat.dim();
+    let values: Vec<u8> = heat.iter().copied().collect();
+    Ok(rgb_array(py, &color_lut, &values, &vec![false; w * h], [0; 4], w, h))
+}
+
+#[pymodule]
+fn rusty_fire(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PySimulation>()?;
+    m.add_function(wrap_pyfunction!(color_map_names, m)?)?;
+    m.add_function(wrap_pyfunction!(color_map, m)?)?;
+    m.add_function(wrap_pyfunction!(apply_color_map, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,82 @@
+"""Tests of the Python bindings. Run `maturin develop` first, then `pytest`
+in the `python` directory."""
+
+import numpy as np
+import pytest
+
+import rusty_fire
+
+
+@pytest.fixture
+def sim():
+    sim = rusty_fire.Simulation(24, 16, {"seed": 42})
+    sim.step(20)
+    return sim
+
+
+def test_arrays_are_row_major(sim):
+    heat = sim.heat()
+    assert heat.shape == (16, 24)
+    assert heat.dtype == np.uint8
+    # the fire burns at the bottom of the grid, the last row
+    assert heat[-1].mean() > heat[0].mean()
+
+    frame = sim.rgb("gray")
+    assert frame.shape == (16, 24, 3)
+    assert frame.dtype == np.uint8
+    table = rusty_fire.color_map("gray")
+    np.testing.assert_array_equal(frame, table[heat])
+    np.testing.assert_array_equal(rusty_fire.apply_color_map(heat, "gray"), frame)
+
+
+def test_obstacles_are_row_major(sim):
+    obstacles = np.zeros((16, 24), dtype=bool)
+    obstacles[2, 5] = True
+    sim.obstacles = obstacles
+    np.testing.assert_array_equal(sim.obstacles, obstacles)
+    with pytest.raises(ValueError):
+        sim.obstacles = obstacles.T
+
+
+def test_color_map_tables(sim):
+    heat = sim.heat()
+    ramp = np.arange(256, dtype=np.uint8)
+
+    uint8_table = np.zeros((256, 3), dtype=np.uint8)
+    uint8_table[:, 0] = ramp
+    frame = sim.rgb(uint8_table)
+    np.testing.assert_array_equal(frame[..., 0], heat)
+    assert not frame[..., 1:].any()
+
+    float_table = np.zeros((256, 4))
+    float_table[:, 0] = ramp / 255
+    float_table[:, 3] = 0.5
+    np.testing.assert_array_equal(sim.rgb(float_table), frame)
+    np.testing.assert_array_equal(rusty_fire.apply_color_map(heat, float_table), frame)
+
+    for table in [np.zeros((255, 3), dtype=np.uint8), np.zeros((256, 2)), np.zeros((256, 5))]:
+        with pytest.raises(ValueError):
+            sim.rgb(table)
+    with pytest.raises(TypeError):
+        sim.rgb(np.zeros((256, 3), dtype=np.int32))
+    with pytest.raises(ValueError):
+        sim.rgb("nothing")
+
+
+def test_update_is_atomic(sim):
+    before = sim.configs
+    with pytest.raises(ValueError):
+        sim.update({"cooling.strength": 0.3, "fill_percentage": "x"})
+    assert sim.configs == before
+
+    sim.update({"cooling.strength": 0.3, "forces.wind": 0.5})
+    assert sim.configs["cooling.strength"] == "0.3"
+    assert sim.configs["forces.wind"] == "0.5"
+
+
+def test_snapshot_round_trip(sim, tmp_path):
+    path = str(tmp_path / "fire.snapshot")
+    sim.save_snapshot(path)
+    restored = rusty_fire.Simulation.load_snapshot(path)
+    assert (restored.width, restored.height, restored.frame) == (24, 16, 20)
+    np.testing.assert_array_equal(restored.heat(), sim.heat())
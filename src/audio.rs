@@ -0,0 +1,630 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read};
+use crate::cli::AppOptions;
+use crate::configs::{AudioConfigs, FireConfigs};
+use crate::control::{apply_change, ConfigChange, ControlTarget};
+
+/// Samples analysed at once, about 46 ms at 44.1 kHz.
+const WINDOW_SIZE: usize = 2048;
+/// Frequencies in Hz of the bass, mid and high bands.
+const BASS_BAND: (f64, f64) = (20.0, 250.0);
+const MID_BAND: (f64, f64) = (250.0, 4000.0);
+const HIGH_BAND: (f64, f64) = (4000.0, 16000.0);
+
+/// What the audio analysis measures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioFeature {
+    /// Loudness of the whole signal.
+    Rms,
+    Bass,
+    Mid,
+    High,
+}
+
+impl AudioFeature {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rms" => Some(AudioFeature::Rms),
+            "bass" => Some(AudioFeature::Bass),
+            "mid" => Some(AudioFeature::Mid),
+            "high" => Some(AudioFeature::High),
+            _ => None,
+        }
+    }
+}
+
+/// The features of a window of audio, as RMS amplitudes or, once taken by
+/// [`AudioLevels::levels`], levels between 0 and 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AudioLevels {
+    pub rms: f64,
+    pub bass: f64,
+    pub mid: f64,
+    pub high: f64,
+}
+
+impl AudioLevels {
+    pub fn get(&self, feature: AudioFeature) -> f64 {
+        match feature {
+            AudioFeature::Rms => self.rms,
+            AudioFeature::Bass => self.bass,
+            AudioFeature::Mid => self.mid,
+            AudioFeature::High => self.high,
+        }
+    }
+
+    /// Spreads the amplitudes in dB over `configs.range`, so a full scale
+    /// sine is 1 and anything `range` dB below it 0.
+    pub fn levels(&self, configs: &AudioConfigs) -> Self {
+        let level = |rms: f64| {
+            if rms <= 0.0 {
+                return 0.0;
+            }
+            let db = 20.0 * (rms * 2f64.sqrt()).log10() + configs.gain;
+            (1.0 + db / configs.range).clamp(0.0, 1.0)
+        };
+        Self { rms: level(self.rms), bass: level(self.bass), mid: level(self.mid), high: level(self.high) }
+    }
+}
+
+/// Encoding of the samples.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SampleFormat {
+    /// Signed integers of the given bits, unsigned for 8 bits as in WAV.
+    Int(u16),
+    Float(u16),
+}
+
+impl SampleFormat {
+    fn bytes(&self) -> usize {
+        match self {
+            SampleFormat::Int(bits) | SampleFormat::Float(bits) => usize::from(*bits).div_ceil(8),
+        }
+    }
+
+    /// Decodes a little endian sample to between -1 and 1.
+    fn decode(&self, bytes: &[u8]) -> f32 {
+        match (self, bytes.len()) {
+            (SampleFormat::Int(_), 1) => (f32::from(bytes[0]) - 128.0) / 128.0,
+            (SampleFormat::Int(_), 2) => f32::from(i16::from_le_bytes([bytes[0], bytes[1]])) / 32768.0,
+            (SampleFormat::Int(_), 3) => {
+                (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as f32 / 8388608.0
+            }
+            (SampleFormat::Int(_), 4) => i32::from_le_bytes(bytes.try_into().unwrap()) as f32 / 2147483648.0,
+            (SampleFormat::Float(_), 4) => f32::from_le_bytes(bytes.try_into().unwrap()),
+            (SampleFormat::Float(_), 8) => f64::from_le_bytes(bytes.try_into().unwrap()) as f32,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Layout of the samples of a WAV file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct WavFormat {
+    format: SampleFormat,
+    channels: usize,
+    sample_rate: u32,
+    /// Bytes per sample of one channel, which may hold padding in front of
+    /// the sample, e.g. 24 bit samples in 32 bit containers.
+    stride: usize,
+}
+
+/// Reads a WAV stream, or raw signed 16 bit little endian PCM if it does not
+/// start like one, mixing the channels down to mono. Keeps the last window of
+/// samples for the analysis.
+pub struct AudioInput {
+    reader: Box<dyn Read>,
+    format: SampleFormat,
+    channels: usize,
+    stride: usize,
+    pub sample_rate: u32,
+    /// Bytes left in the data chunk, `None` if it runs to the end of the stream.
+    remaining: Option<u64>,
+    /// The last samples read, oldest first.
+    history: VecDeque<f32>,
+    /// Samples read so far, and silence after the end.
+    position: u64,
+    ended: bool,
+    frame: Vec<u8>,
+}
+
+impl AudioInput {
+    /// Opens the file at `path`, or standard input if it is `-`. `raw_rate`
+    /// and `raw_channels` describe raw PCM, WAV files describe themselves.
+    pub fn open(path: &str, raw_rate: u32, raw_channels: u16) -> Result<Self, String> {
+        let name = if path == "-" { "standard input" } else { path };
+        let error = |e: std::io::Error| format!("could not read audio from {}: {}", name, e);
+        let mut reader: Box<dyn Read> = if path == "-" {
+            Box::new(BufReader::new(std::io::stdin()))
+        } else {
+            Box::new(BufReader::new(File::open(path).map_err(error)?))
+        };
+
+        let mut magic = Vec::new();
+        reader.by_ref().take(4).read_to_end(&mut magic).map_err(error)?;
+        if magic != b"RIFF" {
+            // put the bytes back in front of the raw samples
+            let reader = Box::new(Cursor::new(magic).chain(reader));
+            let format = WavFormat {
+                format: SampleFormat::Int(16),
+                channels: usize::from(raw_channels),
+                sample_rate: raw_rate,
+                stride: 2,
+            };
+            return Ok(Self::new(reader, format, None));
+        }
+
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).map_err(error)?;
+        if &header[4..] != b"WAVE" {
+            return Err(format!("{} is not a WAV file", name));
+        }
+        let mut format = None;
+        loop {
+            reader.read_exact(&mut header).map_err(error)?;
+            let size = u32::from_le_bytes(header[4..].try_into().unwrap());
+            match &header[..4] {
+                b"fmt " => {
+                    if size > 1024 {
+                        return Err(format!("{} has an invalid format chunk", name));
+                    }
+                    let mut chunk = vec![0u8; size as usize + (size as usize & 1)];
+                    reader.read_exact(&mut chunk).map_err(error)?;
+                    format = Some(parse_format_chunk(&chunk).map_err(|e| format!("{}: {}", name, e))?);
+                }
+                b"data" => {
+                    let format = format.ok_or_else(|| format!("{} has no format before its data", name))?;
+                    // streamed WAV files do not know their length
+                    let remaining = (size != 0 && size != u32::MAX).then_some(u64::from(size));
+                    return Ok(Self::new(reader, format, remaining));
+                }
+                _ => {
+                    let skip = u64::from(size) + u64::from(size & 1);
+                    std::io::copy(&mut reader.by_ref().take(skip), &mut std::io::sink()).map_err(error)?;
+                }
+            }
+        }
+    }
+
+    fn new(reader: Box<dyn Read>, format: WavFormat, remaining: Option<u64>) -> Self {
+        Self {
+            reader,
+            format: format.format,
+            channels: format.channels,
+            stride: format.stride,
+            sample_rate: format.sample_rate,
+            remaining,
+            history: VecDeque::from(vec![0.0; WINDOW_SIZE]),
+            position: 0,
+            ended: false,
+            frame: vec![0u8; format.stride * format.channels],
+        }
+    }
+
+    /// The next sample mixed down to mono, `None` at the end of the stream.
+    fn read_sample(&mut self) -> Option<f32> {
+        if let Some(remaining) = &mut self.remaining {
+            *remaining = remaining.checked_sub(self.frame.len() as u64)?;
+        }
+        self.reader.read_exact(&mut self.frame).ok()?;
+        // padding sits in front of the sample, in its least significant bytes
+        let padding = self.stride - self.format.bytes();
+        let sum: f32 = self.frame.chunks_exact(self.stride).map(|bytes| self.format.decode(&bytes[padding..])).sum();
+        Some(sum / self.channels as f32)
+    }
+
+    /// Reads on up to the sample at `position`, blocking until a stream
+    /// delivers it. After the end the audio is silent.
+    pub fn advance_to(&mut self, position: u64) {
+        while self.position < position {
+            if self.ended {
+                let silent = (position - self.position).min(WINDOW_SIZE as u64) as usize;
+                self.history.drain(..silent);
+                self.history.resize(WINDOW_SIZE, 0.0);
+                self.position = position;
+                return;
+            }
+            let sample = self.read_sample().unwrap_or_else(|| {
+                self.ended = true;
+                0.0
+            });
+            self.history.pop_front();
+            self.history.push_back(sample);
+            self.position += 1;
+        }
+    }
+
+    /// The last samples up to the current position.
+    pub fn window(&self) -> &VecDeque<f32> {
+        &self.history
+    }
+}
+
+/// Parses a `fmt ` chunk into the layout of the samples.
+fn parse_format_chunk(chunk: &[u8]) -> Result<WavFormat, String> {
+    if chunk.len() < 16 {
+        return Err(String::from("invalid format chunk"));
+    }
+    let u16_at = |i: usize| u16::from_le_bytes([chunk[i], chunk[i + 1]]);
+    let mut tag = u16_at(0);
+    let channels = usize::from(u16_at(2));
+    let rate = u32::from_le_bytes(chunk[4..8].try_into().unwrap());
+    let block_align = usize::from(u16_at(12));
+    let bits = u16_at(14);
+    // WAVE_FORMAT_EXTENSIBLE keeps the actual format in its sub format
+    if tag == 0xfffe && chunk.len() >= 26 {
+        tag = u16_at(24);
+    }
+    let format = match (tag, bits) {
+        (1, 8 | 16 | 24 | 32) => SampleFormat::Int(bits),
+        (3, 32 | 64) => SampleFormat::Float(bits),
+        _ => return Err(format!("unsupported WAV format {} with {} bits", tag, bits)),
+    };
+    if channels == 0 || rate == 0 {
+        return Err(String::from("invalid format chunk"));
+    }
+    // some writers leave the block align at 0, the samples are packed then
+    let stride = if block_align == 0 { format.bytes() } else { block_align / channels };
+    if block_align % channels != 0 || stride < format.bytes() {
+        return Err(format!("invalid block align {} for {} channels of {} bits", block_align, channels, bits));
+    }
+    Ok(WavFormat { format, channels, sample_rate: rate, stride })
+}
+
+/// In place radix 2 FFT, the length must be a power of two.
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Measures the loudness and the energy of the frequency bands of a window
+/// of samples.
+pub struct AudioAnalyzer {
+    hann: Vec<f64>,
+    hann_power: f64,
+    re: Vec<f64>,
+    im: Vec<f64>,
+}
+
+impl AudioAnalyzer {
+    pub fn new() -> Self {
+        let hann: Vec<f64> = (0..WINDOW_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / WINDOW_SIZE as f64).cos())
+            .collect();
+        let hann_power = hann.iter().map(|w| w * w).sum();
+        Self { hann, hann_power, re: vec![0.0; WINDOW_SIZE], im: vec![0.0; WINDOW_SIZE] }
+    }
+
+    /// RMS amplitudes of the window and of its bands, the bands scaled so a
+    /// sine within one measures like the whole signal.
+    pub fn analyze(&mut self, window: &VecDeque<f32>, sample_rate: u32) -> AudioLevels {
+        let mut power = 0.0;
+        for (i, &sample) in window.iter().enumerate() {
+            let sample = f64::from(sample);
+            power += sample * sample;
+            self.re[i] = sample * self.hann[i];
+            self.im[i] = 0.0;
+        }
+        fft(&mut self.re, &mut self.im);
+
+        let bin_width = f64::from(sample_rate) / WINDOW_SIZE as f64;
+        let band = |(low, high): (f64, f64)| {
+            let first = ((low / bin_width).ceil() as usize).max(1);
+            let last = ((high / bin_width).floor() as usize).min(WINDOW_SIZE / 2 - 1);
+            let energy: f64 = (first..=last).map(|k| self.re[k] * self.re[k] + self.im[k] * self.im[k]).sum();
+            (2.0 * energy / (WINDOW_SIZE as f64 * self.hann_power)).sqrt()
+        };
+        AudioLevels {
+            rms: (power / WINDOW_SIZE as f64).sqrt(),
+            bass: band(BASS_BAND),
+            mid: band(MID_BAND),
+            high: band(HIGH_BAND),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioBinding {
+    pub feature: AudioFeature,
+    pub key: String,
+    pub target: ControlTarget,
+}
+
+/// Which audio feature drives which config value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioMapping {
+    pub bindings: Vec<AudioBinding>,
+}
+
+impl AudioMapping {
+    /// Parses `FEATURE = KEY RANGE` lines, where the feature is `rms`,
+    /// `bass`, `mid` or `high` and the range `FROM..TO` or a comma separated
+    /// list of choices. Empty lines and lines starting with `#` are skipped.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut bindings = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("invalid audio mapping line {}", line);
+            let (feature, assignment) = line.split_once('=').ok_or_else(invalid)?;
+            let (key, target) = assignment.trim().split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let feature = feature.trim();
+            bindings.push(AudioBinding {
+                feature: AudioFeature::from_name(feature)
+                    .ok_or_else(|| format!("unknown audio feature {}", feature))?,
+                key: key.to_string(),
+                target: ControlTarget::parse(target)?,
+            });
+        }
+        Ok(Self { bindings })
+    }
+
+    /// Bass feeds the base of the fire, highs make it spark and loud passages
+    /// cool it less. The two dimensional fire has no sparks, its fire mask
+    /// relights the base at the flicker rate instead.
+    pub fn default_for(strip: bool) -> Self {
+        let text = if strip {
+            "bass = strip.spark_min 120..230\nhigh = strip.sparking 0.2..1\nrms = strip.cooling 16..4\n"
+        } else {
+            "bass = fill_percentage 40..100\nhigh = fire_mask.flicker_rate 0.01..0.4\nrms = cooling.strength 0.2..0.04\n"
+        };
+        Self::parse(text).expect("the default audio mapping is valid")
+    }
+}
+
+/// Modulates the configs by the audio, advancing it by one simulation step
+/// at a time. As the audio follows the simulation time rather than the
+/// clock, the same audio drives the fire the same way in every mode.
+pub struct AudioReactive {
+    input: AudioInput,
+    analyzer: AudioAnalyzer,
+    mapping: AudioMapping,
+    /// Smoothed levels of the features.
+    levels: AudioLevels,
+    /// Simulation time in seconds.
+    time: f64,
+}
+
+impl AudioReactive {
+    /// Opens the audio given in `options` with its mapping, `None` without.
+    pub fn start(options: &AppOptions, fire_configs: &FireConfigs) -> Result<Option<Self>, String> {
+        let Some(path) = &options.audio_path else {
+            return Ok(None);
+        };
+        let mapping = match &options.audio_mapping {
+            Some(mapping_path) => {
+                let text = std::fs::read_to_string(mapping_path)
+                    .map_err(|e| format!("could not read audio mapping {}: {}", mapping_path, e))?;
+                AudioMapping::parse(&text).map_err(|e| format!("{}: {}", mapping_path, e))?
+            }
+            None => AudioMapping::default_for(options.strip),
+        };
+        let input = AudioInput::open(path, options.audio_rate, options.audio_channels)?;
+        Self::new(input, mapping, fire_configs).map(Some)
+    }
+
+    /// Checks that `mapping` only sets valid values of `fire_configs`.
+    pub fn new(input: AudioInput, mapping: AudioMapping, fire_configs: &FireConfigs) -> Result<Self, String> {
+        let mut configs = fire_configs.clone();
+        for binding in &mapping.bindings {
+            // a new table every step would be too slow
+            if binding.key == "color_map" {
+                return Err(String::from("the color map cannot follow the audio"));
+            }
+            for level in [0.0, 1.0] {
+                let change = ConfigChange { key: binding.key.clone(), value: binding.target.level(level) };
+                apply_change(&mut configs, &change).map_err(|e| format!("audio mapping: {}", e))?;
+            }
+        }
+        Ok(Self { input, analyzer: AudioAnalyzer::new(), mapping, levels: AudioLevels::default(), time: 0.0 })
+    }
+
+    /// Sets the mapped configs for the next simulation step.
+    pub fn step(&mut self, fire_configs: &mut FireConfigs) {
+        let position = (self.time * f64::from(self.input.sample_rate)).round() as u64;
+        self.input.advance_to(position);
+        let measured = self
+            .analyzer
+            .analyze(self.input.window(), self.input.sample_rate)
+            .levels(&fire_configs.audio_configs);
+
+        let dt = 1.0 / fire_configs.timing_configs.simulation_rate;
+        let configs = &fire_configs.audio_configs;
+        let smooth = |level: &mut f64, measured: f64| {
+            let seconds = if measured > *level { configs.attack } else { configs.release };
+            let weight = if seconds > 0.0 { 1.0 - (-dt / seconds).exp() } else { 1.0 };
+            *level += (measured - *level) * weight;
+        };
+        smooth(&mut self.levels.rms, measured.rms);
+        smooth(&mut self.levels.bass, measured.bass);
+        smooth(&mut self.levels.mid, measured.mid);
+        smooth(&mut self.levels.high, measured.high);
+        self.time += dt;
+
+        for binding in &self.mapping.bindings {
+            let value = binding.target.level(self.levels.get(binding.feature));
+            // the mapping was checked when it was loaded
+            let _ = apply_change(fire_configs, &ConfigChange { key: binding.key.clone(), value });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use crate::simulation::FireSimulation;
+    use super::*;
+
+    const RATE: u32 = 44100;
+
+    fn sine(frequency: f64, amplitude: f64, samples: usize) -> Vec<f64> {
+        (0..samples).map(|i| amplitude * (2.0 * PI * frequency * i as f64 / f64::from(RATE)).sin()).collect()
+    }
+
+    /// A `fmt ` chunk, `extensible_tag` wraps the format in
+    /// WAVE_FORMAT_EXTENSIBLE with that sub format.
+    fn format_chunk(tag: u16, channels: u16, block_align: u16, bits: u16, extensible_tag: Option<u16>) -> Vec<u8> {
+        let mut chunk = Vec::new();
+        chunk.extend_from_slice(&extensible_tag.map_or(tag, |_| 0xfffe).to_le_bytes());
+        chunk.extend_from_slice(&channels.to_le_bytes());
+        chunk.extend_from_slice(&RATE.to_le_bytes());
+        chunk.extend_from_slice(&(RATE * u32::from(block_align)).to_le_bytes());
+        chunk.extend_from_slice(&block_align.to_le_bytes());
+        chunk.extend_from_slice(&bits.to_le_bytes());
+        if let Some(sub_format) = extensible_tag {
+            chunk.extend_from_slice(&22u16.to_le_bytes());
+            chunk.extend_from_slice(&bits.to_le_bytes()); // valid bits
+            chunk.extend_from_slice(&3u32.to_le_bytes()); // channel mask
+            chunk.extend_from_slice(&sub_format.to_le_bytes());
+            chunk.extend_from_slice(b"\x00\x00\x00\x00\x10\x00\x80\x00\x00\xaa\x00\x38\x9b\x71");
+        }
+        chunk
+    }
+
+    fn wav(format: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut file = b"RIFF".to_vec();
+        file.extend_from_slice(&(4 + 8 + format.len() as u32 + 8 + data.len() as u32).to_le_bytes());
+        file.extend_from_slice(b"WAVEfmt ");
+        file.extend_from_slice(&(format.len() as u32).to_le_bytes());
+        file.extend_from_slice(format);
+        file.extend_from_slice(b"data");
+        file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        file.extend_from_slice(data);
+        file
+    }
+
+    fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rusty-fire-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Mono 16 bit PCM of a bass line under a hi-hat that only plays in the
+    /// second half, so the levels change over time.
+    fn song() -> Vec<u8> {
+        let bass = sine(80.0, 0.5, RATE as usize);
+        let hat = sine(9000.0, 0.3, RATE as usize);
+        let samples = bass.iter().zip(&hat).enumerate().map(|(i, (b, h))| if i < RATE as usize / 2 { *b } else { b + h });
+        let data: Vec<u8> = samples.flat_map(|s| ((s * 32767.0).round() as i16).to_le_bytes()).collect();
+        wav(&format_chunk(1, 1, 2, 16, None), &data)
+    }
+
+    #[test]
+    fn full_scale_sines_land_in_their_band() {
+        let configs = FireConfigs::default().audio_configs;
+        let mut analyzer = AudioAnalyzer::new();
+        for (frequency, feature) in [(100.0, AudioFeature::Bass), (1000.0, AudioFeature::Mid), (8000.0, AudioFeature::High)] {
+            let window: VecDeque<f32> = sine(frequency, 1.0, WINDOW_SIZE).iter().map(|&s| s as f32).collect();
+            let levels = analyzer.analyze(&window, RATE).levels(&configs);
+            assert!((levels.rms - 1.0).abs() < 0.01, "{} Hz rms {}", frequency, levels.rms);
+            for other in [AudioFeature::Bass, AudioFeature::Mid, AudioFeature::High] {
+                let level = levels.get(other);
+                if other == feature {
+                    assert!((level - 1.0).abs() < 0.02, "{} Hz in {:?}: {}", frequency, other, level);
+                } else {
+                    assert!(level < 0.5, "{} Hz leaks into {:?}: {}", frequency, other, level);
+                }
+            }
+        }
+        // 20 dB down is a third of the default 60 dB range below full scale
+        let window: VecDeque<f32> = sine(1000.0, 0.1, WINDOW_SIZE).iter().map(|&s| s as f32).collect();
+        assert!((analyzer.analyze(&window, RATE).levels(&configs).mid - 2.0 / 3.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn parses_format_chunks() {
+        let pcm16 = parse_format_chunk(&format_chunk(1, 2, 4, 16, None)).unwrap();
+        assert_eq!(pcm16, WavFormat { format: SampleFormat::Int(16), channels: 2, sample_rate: RATE, stride: 2 });
+        let float32 = parse_format_chunk(&format_chunk(3, 1, 4, 32, None)).unwrap();
+        assert_eq!(float32, WavFormat { format: SampleFormat::Float(32), channels: 1, sample_rate: RATE, stride: 4 });
+        let extensible = parse_format_chunk(&format_chunk(0, 2, 6, 24, Some(1))).unwrap();
+        assert_eq!(extensible, WavFormat { format: SampleFormat::Int(24), channels: 2, sample_rate: RATE, stride: 3 });
+        let extensible_float = parse_format_chunk(&format_chunk(0, 2, 8, 32, Some(3))).unwrap();
+        assert_eq!(extensible_float.format, SampleFormat::Float(32));
+        // 24 bit samples in 32 bit containers
+        let padded = parse_format_chunk(&format_chunk(1, 2, 8, 24, None)).unwrap();
+        assert_eq!((padded.format, padded.stride), (SampleFormat::Int(24), 4));
+
+        assert!(parse_format_chunk(&format_chunk(1, 2, 4, 12, None)).is_err());
+        assert!(parse_format_chunk(&format_chunk(1, 2, 5, 16, None)).is_err());
+        assert!(parse_format_chunk(&format_chunk(1, 2, 2, 16, None)).is_err());
+        assert!(parse_format_chunk(&format_chunk(1, 0, 4, 16, None)).is_err());
+        assert!(parse_format_chunk(&[1, 0, 1, 0]).is_err());
+    }
+
+    #[test]
+    fn decodes_padded_samples() {
+        // stereo 24 bit in 32 bit containers, the padding byte first
+        let samples = [0x400000i32, -0x400000, 0x200000, 0];
+        let data: Vec<u8> = samples.iter().flat_map(|s| (s << 8).to_le_bytes()).collect();
+        let path = temp_file("padded.wav", &wav(&format_chunk(1, 2, 8, 24, None), &data));
+        let mut input = AudioInput::open(path.to_str().unwrap(), RATE, 2).unwrap();
+        input.advance_to(3);
+        std::fs::remove_file(path).unwrap();
+        let window: Vec<f32> = input.window().iter().rev().take(3).copied().collect();
+        // mixed down to mono, the stream ended after the second frame
+        assert_eq!(window, [0.0, 0.125, 0.0]);
+    }
+
+    #[test]
+    fn same_audio_drives_the_same_fire() {
+        let path = temp_file("song.wav", &song());
+        let run = || {
+            let mut fire_configs = FireConfigs::default();
+            fire_configs.apply_text("seed=3\nfire_mask.mode=flicker").unwrap();
+            let input = AudioInput::open(path.to_str().unwrap(), RATE, 1).unwrap();
+            let mut audio = AudioReactive::new(input, AudioMapping::default_for(false), &fire_configs).unwrap();
+            let mut simulation = FireSimulation::new(&fire_configs, 32, 24);
+            let mut configs_per_step = Vec::new();
+            for _ in 0..fire_configs.timing_configs.simulation_rate as usize {
+                audio.step(&mut fire_configs);
+                simulation.step(&fire_configs);
+                configs_per_step.push(fire_configs.to_text());
+            }
+            (configs_per_step, simulation.buf)
+        };
+        let (first_configs, first_buf) = run();
+        let (second_configs, second_buf) = run();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(first_configs, second_configs);
+        assert_eq!(first_buf, second_buf);
+        // the hi-hat in the second half raises the flicker rate
+        let flicker_rate = |text: &str| {
+            let line = text.lines().find(|line| line.starts_with("fire_mask.flicker_rate")).unwrap();
+            line.split_once('=').unwrap().1.trim().parse::<f64>().unwrap()
+        };
+        assert!(flicker_rate(&first_configs[55]) > flicker_rate(&first_configs[25]) + 0.1);
+    }
+}
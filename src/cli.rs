@@ -0,0 +1,272 @@
+use std::net::IpAddr;
+use crate::configs::FireConfigs;
+use crate::control::ControlTarget;
+use crate::defaults::{
+    DEFAULT_AUDIO_CHANNELS, DEFAULT_AUDIO_SAMPLE_RATE, DEFAULT_PRE_ROLL_MAX_FRAMES,
+    DEFAULT_PRE_ROLL_TOLERANCE, DEFAULT_PRE_ROLL_WINDOW, DEFAULT_MIDI_MAPPING_PATH, DEFAULT_OSC_BIND_ADDRESS,
+    DEFAULT_SCREENSHOT_DIR, DEFAULT_SNAPSHOT_PATH, DEFAULT_TERMINAL_FPS,
+};
+use crate::export::ExportOptions;
+use crate::screenshot::load_screenshot_configs;
+use crate::terminal::TerminalMode;
+use crate::simulation::PreRoll;
+
+pub const USAGE: &str = "\
+Usage: rusty-fire [OPTIONS]
+
+Options:
+    --seed <SEED>            Seed of the random number generator
+    --obstacle-mask <PATH>   Image whose bright pixels mark solid obstacles
+    --set <KEY>=<VALUE>      Set any config value, e.g. --set cooling.strength=0.2
+    --snapshot <PATH>        File the snapshot hotkeys save to and load from
+    --load-snapshot <PATH>   Resume from a snapshot, including its configs
+    --pre-roll <FRAMES|auto> Simulate ahead before the first frame is shown,
+                             either a number of frames or until the mean heat
+                             has settled
+    --stats-csv <PATH>       Log frame and stage timings to a CSV file
+    --screenshot-dir <DIR>   Directory the screenshot hotkey saves PNGs to
+    --configs-from <PNG>     Take the configs embedded in a screenshot and,
+                             unless --pre-roll is given, pre-roll to its frame
+    --export <PATH>          Render a video with ffmpeg without opening a window
+    --export-codec <CODEC>   Video codec passed to ffmpeg [default: libx264]
+    --export-fps <FPS>       Frame rate of the video [default: 30]
+    --export-size <WxH>      Size of the video [default: the grid size]
+    --export-duration <SECS> Length of the video [default: 10]
+    --export-loop <SECS>     Cross-fade the end of the video into its start
+                             over this many seconds, so it loops seamlessly
+    --terminal <MODE>        Render in the terminal instead of a window, MODE
+                             is auto, truecolor, 256, ascii, sixel or kitty
+    --strip                  Simulate a one dimensional fire for LED strips,
+                             configured with the strip.* configs
+    --terminal-fps <FPS>     Frames drawn per second in the terminal [default: 30]
+    --output <PROTOCOL>      Send the frames to LEDs, PROTOCOL is opc, e131,
+                             artnet, serial or adalight, configured with the
+                             output.* configs
+    --output-address <HOST>  Host and optional port of the LED controller, or
+                             its serial device
+    --pixel-map <PATH>       CSV or JSON layout of the LEDs to sample the fire at
+    --headless               Only drive the LED output and the HTTP server,
+                             without a window
+    --osc-port <PORT>        Listen for OSC messages setting configs on this
+                             UDP port, e.g. /fire/cooling/strength 0.2
+    --osc-bind <ADDRESS>     Address to listen for OSC on, 0.0.0.0 accepts
+                             messages from the whole network
+                             [default: 127.0.0.1]
+    --midi-device <PATH>     Raw MIDI device to take control changes from,
+                             e.g. /dev/snd/midiC1D0
+    --midi-mapping <PATH>    Which controller sets which config
+                             [default: rusty-fire.midimap]
+    --midi-learn <KEY[=RANGE]>
+                             Bind the next unmapped controller moved to KEY
+                             and save it to the mapping, RANGE is MIN..MAX or
+                             a list of choices. Can be given several times
+    --audio <PATH>           WAV file, or - for standard input, whose loudness
+                             and bass, mid and high bands modulate the configs
+    --audio-mapping <PATH>   Which audio feature sets which config
+                             [default: bass, highs and loudness drive the
+                             fill, sparks and cooling]
+    --audio-rate <HZ>        Sample rate of raw PCM audio [default: 44100]
+    --audio-channels <N>     Channels of raw PCM audio [default: 2]
+    --http-port <PORT>       Serve a control API and a live preview stream
+                             on this localhost port, see the README
+    -h, --help               Print this help";
+
+/// Options of the application itself, as opposed to the fire configs.
+pub struct AppOptions {
+    pub snapshot_path: String,
+    pub load_snapshot: Option<String>,
+    pub pre_roll: Option<PreRoll>,
+    pub stats_csv: Option<String>,
+    pub screenshot_dir: String,
+    /// Path of the video to export instead of showing a window.
+    pub export_path: Option<String>,
+    pub export: ExportOptions,
+    /// Render in the terminal instead of a window.
+    pub terminal: Option<TerminalMode>,
+    pub terminal_fps: f64,
+    /// Simulate a strip of LEDs instead of the two dimensional fire.
+    pub strip: bool,
+    /// Only drive the LED output and the HTTP server, without a window.
+    pub headless: bool,
+    pub osc_port: Option<u16>,
+    pub osc_bind: IpAddr,
+    pub midi_device: Option<String>,
+    pub midi_mapping: String,
+    /// Configs to bind to the next controllers moved, in order.
+    pub midi_learn: Vec<(String, ControlTarget)>,
+    pub http_port: Option<u16>,
+    /// WAV file or raw PCM, `-` for standard input, modulating the configs.
+    pub audio_path: Option<String>,
+    pub audio_mapping: Option<String>,
+    /// Format of raw signed 16 bit PCM, WAV files describe themselves.
+    pub audio_rate: u32,
+    pub audio_channels: u16,
+}
+
+impl AppOptions {
+    fn default() -> Self {
+        Self {
+            snapshot_path: String::from(DEFAULT_SNAPSHOT_PATH),
+            load_snapshot: None,
+            pre_roll: None,
+            stats_csv: None,
+            screenshot_dir: String::from(DEFAULT_SCREENSHOT_DIR),
+            export_path: None,
+            export: ExportOptions::default(),
+            terminal: None,
+            terminal_fps: DEFAULT_TERMINAL_FPS,
+            strip: false,
+            headless: false,
+            osc_port: None,
+            osc_bind: DEFAULT_OSC_BIND_ADDRESS,
+            midi_device: None,
+            midi_mapping: String::from(DEFAULT_MIDI_MAPPING_PATH),
+            midi_learn: Vec::new(),
+            http_port: None,
+            audio_path: None,
+            audio_mapping: None,
+            audio_rate: DEFAULT_AUDIO_SAMPLE_RATE,
+            audio_channels: DEFAULT_AUDIO_CHANNELS,
+        }
+    }
+}
+
+/// Applies the command line arguments (without the program name) to
+/// `fire_configs`. Returns `Ok(None)` if the help was requested.
+pub fn parse_args(
+    fire_configs: &mut FireConfigs,
+    args: impl IntoIterator<Item = String>,
+) -> Result<Option<AppOptions>, String> {
+    let mut options = AppOptions::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next().ok_or_else(|| format!("missing value for {}", name))
+        };
+        match arg.as_str() {
+            "--seed" => fire_configs.set("seed", &value(&arg)?)?,
+            "--obstacle-mask" => fire_configs.set("obstacles.mask_path", &value(&arg)?)?,
+            "--set" => {
+                let assignment = value(&arg)?;
+                let (key, val) = assignment
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected KEY=VALUE, got {}", assignment))?;
+                fire_configs.set(key.trim(), val)?;
+            }
+            "--snapshot" => options.snapshot_path = value(&arg)?,
+            "--load-snapshot" => options.load_snapshot = Some(value(&arg)?),
+            "--pre-roll" => {
+                let frames = value(&arg)?;
+                options.pre_roll = Some(match frames.as_str() {
+                    "auto" => PreRoll::Converge {
+                        window: DEFAULT_PRE_ROLL_WINDOW,
+                        tolerance: DEFAULT_PRE_ROLL_TOLERANCE,
+                        max_frames: DEFAULT_PRE_ROLL_MAX_FRAMES,
+                    },
+                    _ => PreRoll::Frames(
+                        frames.parse().map_err(|_| format!("invalid pre-roll {}", frames))?,
+                    ),
+                });
+            }
+            "--stats-csv" => options.stats_csv = Some(value(&arg)?),
+            "--screenshot-dir" => options.screenshot_dir = value(&arg)?,
+            "--configs-from" => {
+                let (configs, frame) = load_screenshot_configs(&value(&arg)?)?;
+                *fire_configs = configs;
+                options.pre_roll.get_or_insert(PreRoll::Frames(frame));
+            }
+            "--export" => options.export_path = Some(value(&arg)?),
+            "--export-codec" => options.export.codec = value(&arg)?,
+            "--export-fps" => options.export.fps = parse_positive(&arg, &value(&arg)?)?,
+            "--export-size" => {
+                let size = value(&arg)?;
+                options.export.size = Some(
+                    size.split_once('x')
+                        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                        .ok_or_else(|| format!("invalid size {}, expected WxH", size))?,
+                );
+            }
+            "--export-duration" => options.export.duration = parse_positive(&arg, &value(&arg)?)?,
+            "--export-loop" => {
+                let seconds = value(&arg)?;
+                options.export.crossfade = seconds
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|seconds| *seconds >= 0.0)
+                    .ok_or_else(|| format!("invalid value {} for {}", seconds, arg))?;
+            }
+            "--terminal" => {
+                let mode = value(&arg)?;
+                options.terminal = Some(
+                    TerminalMode::from_name(&mode)
+                        .ok_or_else(|| format!("invalid terminal mode {}", mode))?,
+                );
+            }
+            "--terminal-fps" => options.terminal_fps = parse_positive(&arg, &value(&arg)?)?,
+            "--strip" => options.strip = true,
+            "--output" => fire_configs.set("output.protocol", &value(&arg)?)?,
+            "--output-address" => fire_configs.set("output.address", &value(&arg)?)?,
+            "--pixel-map" => fire_configs.set("output.map_path", &value(&arg)?)?,
+            "--headless" => options.headless = true,
+            "--osc-port" => {
+                let port = value(&arg)?;
+                options.osc_port = Some(port.parse().map_err(|_| format!("invalid port {}", port))?);
+            }
+            "--osc-bind" => {
+                let address = value(&arg)?;
+                options.osc_bind = address.parse().map_err(|_| format!("invalid address {}", address))?;
+            }
+            "--midi-device" => options.midi_device = Some(value(&arg)?),
+            "--midi-mapping" => options.midi_mapping = value(&arg)?,
+            "--midi-learn" => {
+                let learn = value(&arg)?;
+                let (key, range) = match learn.split_once('=') {
+                    Some((key, range)) => (key.trim(), Some(range)),
+                    None => (learn.trim(), None),
+                };
+                if !fire_configs.entries().iter().any(|(entry, _)| *entry == key) {
+                    return Err(format!("unknown config {}", key));
+                }
+                let target = match range {
+                    Some(range) => ControlTarget::parse(range)?,
+                    None => ControlTarget::default_for(key)
+                        .ok_or_else(|| format!("no default range for {}, use {}=MIN..MAX", key, key))?,
+                };
+                options.midi_learn.push((key.to_string(), target));
+            }
+            "--http-port" => {
+                let port = value(&arg)?;
+                options.http_port = Some(port.parse().map_err(|_| format!("invalid port {}", port))?);
+            }
+            "--audio" => options.audio_path = Some(value(&arg)?),
+            "--audio-mapping" => options.audio_mapping = Some(value(&arg)?),
+            "--audio-rate" => {
+                let rate = value(&arg)?;
+                options.audio_rate = rate
+                    .parse()
+                    .ok()
+                    .filter(|&rate| rate > 0)
+                    .ok_or_else(|| format!("invalid value {} for {}", rate, arg))?;
+            }
+            "--audio-channels" => {
+                let channels = value(&arg)?;
+                options.audio_channels = channels
+                    .parse()
+                    .ok()
+                    .filter(|&channels| channels > 0)
+                    .ok_or_else(|| format!("invalid value {} for {}", channels, arg))?;
+            }
+            "-h" | "--help" => return Ok(None),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    Ok(Some(options))
+}
+
+fn parse_positive(name: &str, value: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|val| *val > 0.0)
+        .ok_or_else(|| format!("invalid value {} for {}", value, name))
+}
@@ -1,4 +1,5 @@
 use macroquad::color::{Color};
+use crate::color_map_listed::{INFERNO_LUT, MAGMA_LUT, PLASMA_LUT, VIRIDIS_LUT};
 
 pub trait ValueToColor {
     fn value_to_color(&self, value: u8, alpha: Option<u8>) -> Color;
@@ -43,3 +44,45 @@ impl ValueToColor for GrayColorMap {
         )
     }
 }
+
+/// Colors of all 256 values precomputed as RGBA bytes, so converting a frame
+/// is a plain table lookup written straight into the image data.
+pub struct ColorLut {
+    lut: [[u8; 4]; 256],
+}
+
+impl ColorLut {
+    /// Table of the color map called `name`, gray for unknown names.
+    pub fn named(name: &str) -> Self {
+        // TODO: Properly handle colormaps
+        let color_map: Box<dyn ValueToColor> = match name.to_ascii_lowercase().as_str() {
+            "gray" => Box::new(GrayColorMap::new()),
+            "magma" => Box::new(ListedColorMap::new(MAGMA_LUT)),
+            "inferno" => Box::new(ListedColorMap::new(INFERNO_LUT)),
+            "plasma" => Box::new(ListedColorMap::new(PLASMA_LUT)),
+            "viridis" => Box::new(ListedColorMap::new(VIRIDIS_LUT)),
+            _ => Box::new(GrayColorMap::new()),
+        };
+        Self::new(color_map.as_ref())
+    }
+
+    pub fn new(color_map: &(impl ValueToColor + ?Sized)) -> Self {
+        let mut lut = [[0u8; 4]; 256];
+        for (value, color) in lut.iter_mut().enumerate() {
+            *color = color_map.value_to_color(value as u8, None).into();
+        }
+        Self { lut }
+    }
+
+    pub fn color(&self, value: u8) -> [u8; 4] {
+        self.lut[value as usize]
+    }
+
+    /// Writes the colors of `values` into `pixels`, except where `mask` is set,
+    /// those pixels get `mask_color`.
+    pub fn apply_masked(&self, values: &[u8], mask: &[bool], mask_color: [u8; 4], pixels: &mut [[u8; 4]]) {
+        for ((pixel, &value), &masked) in pixels.iter_mut().zip(values).zip(mask) {
+            *pixel = if masked { mask_color } else { self.lut[value as usize] };
+        }
+    }
+}
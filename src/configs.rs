@@ -1,51 +1,455 @@
 use crate::defaults::*;
+use crate::snapshot::MAX_CELLS;
 
+/// Largest accepted number of fire mask hot spots.
+const MAX_HOT_SPOT_COUNT: usize = 1024;
+
+/// Largest accepted number of fluid solver iterations per step.
+const MAX_FLUID_ITERATIONS: usize = 1000;
+
+/// Largest accepted number of simulation steps run to catch up in one frame.
+const MAX_STEPS_PER_FRAME: u32 = 1000;
+
+#[derive(Clone)]
 pub struct FireConfigs {
     pub seed: Option<u64>,
+    pub simulation_mode: SimulationMode,
     pub fill_percentage: u8,
     pub base_height: usize,
     pub color_map_name: String,
     pub cooling_map_configs: CoolingMapConfigs,
+    pub fire_mask_configs: FireMaskConfigs,
+    pub force_configs: ForceConfigs,
+    pub fluid_configs: FluidConfigs,
+    pub obstacle_configs: ObstacleConfigs,
+    pub fuel_configs: FuelConfigs,
+    pub strip_configs: StripConfigs,
+    pub output_configs: OutputConfigs,
+    pub audio_configs: AudioConfigs,
+    pub timing_configs: TimingConfigs,
 }
 
 impl FireConfigs {
+    #[allow(dead_code, clippy::too_many_arguments)]
     fn new(
         seed: Option<u64>,
+        simulation_mode: SimulationMode,
         fill_percentage: u8,
         base_height: usize,
         color_map_name: String,
         cooling_map_configs: CoolingMapConfigs,
+        fire_mask_configs: FireMaskConfigs,
+        force_configs: ForceConfigs,
+        fluid_configs: FluidConfigs,
+        obstacle_configs: ObstacleConfigs,
+        fuel_configs: FuelConfigs,
+        strip_configs: StripConfigs,
+        output_configs: OutputConfigs,
+        audio_configs: AudioConfigs,
+        timing_configs: TimingConfigs,
     ) -> Self {
         Self {
             seed,
+            simulation_mode,
             fill_percentage,
             base_height,
             color_map_name,
             cooling_map_configs,
+            fire_mask_configs,
+            force_configs,
+            fluid_configs,
+            obstacle_configs,
+            fuel_configs,
+            strip_configs,
+            output_configs,
+            audio_configs,
+            timing_configs,
         }
     }
 
     pub fn default() -> Self {
         Self {
             seed: None,
+            simulation_mode: SimulationMode::Classic,
             fill_percentage: DEFAULT_FILL_PERCENTAGE,
             base_height: DEFAULT_FIRE_BASE_HEIGHT,
             color_map_name: String::from("Gray"),
             cooling_map_configs: CoolingMapConfigs::default(),
+            fire_mask_configs: FireMaskConfigs::default(),
+            force_configs: ForceConfigs::default(),
+            fluid_configs: FluidConfigs::default(),
+            obstacle_configs: ObstacleConfigs::default(),
+            fuel_configs: FuelConfigs::default(),
+            strip_configs: StripConfigs::default(),
+            output_configs: OutputConfigs::default(),
+            audio_configs: AudioConfigs::default(),
+            timing_configs: TimingConfigs::default(),
+        }
+    }
+
+    /// All configuration values as `(key, value)` pairs. Every pair can be fed
+    /// back into [`FireConfigs::set`].
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let cooling = &self.cooling_map_configs;
+        let mask = &self.fire_mask_configs;
+        let forces = &self.force_configs;
+        let fluid = &self.fluid_configs;
+        let obstacles = &self.obstacle_configs;
+        let fuel = &self.fuel_configs;
+        let strip = &self.strip_configs;
+        let output = &self.output_configs;
+        let audio = &self.audio_configs;
+        let timing = &self.timing_configs;
+        vec![
+            ("seed", self.seed.map_or(String::from("none"), |seed| seed.to_string())),
+            ("simulation_mode", self.simulation_mode.name().to_string()),
+            ("fill_percentage", self.fill_percentage.to_string()),
+            ("base_height", self.base_height.to_string()),
+            ("color_map", self.color_map_name.clone()),
+            ("cooling.length_scale", cooling.length_scale.to_string()),
+            ("cooling.strength", cooling.strength.to_string()),
+            ("fire_mask.mode", mask.mode.name().to_string()),
+            ("fire_mask.flicker_rate", mask.flicker_rate.to_string()),
+            ("fire_mask.length_scale", mask.length_scale.to_string()),
+            ("fire_mask.speed", mask.speed.to_string()),
+            ("fire_mask.hot_spot_count", mask.hot_spot_count.to_string()),
+            ("fire_mask.hot_spot_width", mask.hot_spot_width.to_string()),
+            ("forces.wind", forces.wind.to_string()),
+            ("forces.gust_strength", forces.gust_strength.to_string()),
+            ("forces.gust_length_scale", forces.gust_length_scale.to_string()),
+            ("forces.gust_speed", forces.gust_speed.to_string()),
+            ("forces.buoyancy", forces.buoyancy.to_string()),
+            ("fluid.resolution_scale", fluid.resolution_scale.to_string()),
+            ("fluid.time_step", fluid.time_step.to_string()),
+            ("fluid.iterations", fluid.iterations.to_string()),
+            ("fluid.viscosity", fluid.viscosity.to_string()),
+            ("fluid.buoyancy", fluid.buoyancy.to_string()),
+            ("fluid.drag", fluid.drag.to_string()),
+            ("fluid.vorticity", fluid.vorticity.to_string()),
+            ("fluid.fuel_injection", fluid.fuel_injection.to_string()),
+            ("fluid.burn_rate", fluid.burn_rate.to_string()),
+            ("fluid.heat_release", fluid.heat_release.to_string()),
+            ("fluid.ignition_temperature", fluid.ignition_temperature.to_string()),
+            ("fluid.source_temperature", fluid.source_temperature.to_string()),
+            ("fluid.cooling", fluid.cooling.to_string()),
+            ("fluid.cooling_map_coupling", fluid.cooling_map_coupling.to_string()),
+            ("obstacles.mask_path", obstacles.mask_path.clone().unwrap_or_else(|| String::from("none"))),
+            ("obstacles.brush_radius", obstacles.brush_radius.to_string()),
+            ("obstacles.color", format!("{},{},{}", obstacles.color[0], obstacles.color[1], obstacles.color[2])),
+            ("fuel.enabled", fuel.enabled.to_string()),
+            ("fuel.bed_height", fuel.bed_height.to_string()),
+            ("fuel.amount", fuel.amount.to_string()),
+            ("fuel.burn_rate", fuel.burn_rate.to_string()),
+            ("fuel.ignition_temperature", fuel.ignition_temperature.to_string()),
+            ("fuel.flame_temperature", fuel.flame_temperature.to_string()),
+            ("fuel.spread_chance", fuel.spread_chance.to_string()),
+            ("fuel.ignition_position", fuel.ignition_position.to_string()),
+            ("strip.length", strip.length.to_string()),
+            ("strip.layout", strip.layout.name().to_string()),
+            ("strip.reversed", strip.reversed.to_string()),
+            ("strip.cooling", strip.cooling.to_string()),
+            ("strip.sparking", strip.sparking.to_string()),
+            ("strip.spark_zone", strip.spark_zone.to_string()),
+            ("strip.spark_min", strip.spark_min.to_string()),
+            ("strip.spark_max", strip.spark_max.to_string()),
+            ("output.protocol", output.protocol.name().to_string()),
+            ("output.address", output.address.clone().unwrap_or_else(|| String::from("none"))),
+            ("output.baud_rate", output.baud_rate.to_string()),
+            ("output.universe", output.universe.to_string()),
+            ("output.channels_per_universe", output.channels_per_universe.to_string()),
+            ("output.channel_offset", output.channel_offset.to_string()),
+            ("output.map_path", output.map_path.clone().unwrap_or_else(|| String::from("none"))),
+            ("output.width", output.width.to_string()),
+            ("output.height", output.height.to_string()),
+            ("output.serpentine", output.serpentine.to_string()),
+            ("output.gamma", output.gamma.to_string()),
+            ("output.brightness", output.brightness.to_string()),
+            ("output.fps", output.fps.to_string()),
+            ("audio.gain", audio.gain.to_string()),
+            ("audio.range", audio.range.to_string()),
+            ("audio.attack", audio.attack.to_string()),
+            ("audio.release", audio.release.to_string()),
+            ("timing.simulation_rate", timing.simulation_rate.to_string()),
+            ("timing.max_steps_per_frame", timing.max_steps_per_frame.to_string()),
+            ("timing.interpolate", timing.interpolate.to_string()),
+        ]
+    }
+
+    /// Sets the configuration value `key`, as listed by
+    /// [`FireConfigs::entries`], from its textual form.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let cooling = &mut self.cooling_map_configs;
+        let mask = &mut self.fire_mask_configs;
+        let forces = &mut self.force_configs;
+        let fluid = &mut self.fluid_configs;
+        let obstacles = &mut self.obstacle_configs;
+        let fuel = &mut self.fuel_configs;
+        let strip = &mut self.strip_configs;
+        let output = &mut self.output_configs;
+        let audio = &mut self.audio_configs;
+        let timing = &mut self.timing_configs;
+        match key {
+            "seed" => self.seed = parse_optional(key, value)?,
+            "simulation_mode" => {
+                self.simulation_mode = SimulationMode::from_name(value)
+                    .ok_or_else(|| invalid_value(key, value))?;
+            }
+            "fill_percentage" => self.fill_percentage = parse(key, value)?,
+            "base_height" => self.base_height = parse_at_most(key, value, MAX_CELLS)?,
+            "color_map" => self.set_color_map_name(value.to_string()),
+            "cooling.length_scale" => cooling.length_scale = parse(key, value)?,
+            "cooling.strength" => cooling.strength = parse(key, value)?,
+            "fire_mask.mode" => {
+                mask.mode = FireMaskMode::from_name(value)
+                    .ok_or_else(|| invalid_value(key, value))?;
+            }
+            "fire_mask.flicker_rate" => mask.flicker_rate = parse(key, value)?,
+            "fire_mask.length_scale" => mask.length_scale = parse(key, value)?,
+            "fire_mask.speed" => mask.speed = parse(key, value)?,
+            "fire_mask.hot_spot_count" => mask.hot_spot_count = parse_at_most(key, value, MAX_HOT_SPOT_COUNT)?,
+            "fire_mask.hot_spot_width" => mask.hot_spot_width = parse(key, value)?,
+            "forces.wind" => forces.wind = parse(key, value)?,
+            "forces.gust_strength" => forces.gust_strength = parse(key, value)?,
+            "forces.gust_length_scale" => forces.gust_length_scale = parse(key, value)?,
+            "forces.gust_speed" => forces.gust_speed = parse(key, value)?,
+            "forces.buoyancy" => forces.buoyancy = parse(key, value)?,
+            "fluid.resolution_scale" => fluid.resolution_scale = parse_at_most(key, value, MAX_CELLS)?,
+            "fluid.time_step" => fluid.time_step = parse(key, value)?,
+            "fluid.iterations" => fluid.iterations = parse_at_most(key, value, MAX_FLUID_ITERATIONS)?,
+            "fluid.viscosity" => fluid.viscosity = parse(key, value)?,
+            "fluid.buoyancy" => fluid.buoyancy = parse(key, value)?,
+            "fluid.drag" => fluid.drag = parse(key, value)?,
+            "fluid.vorticity" => fluid.vorticity = parse(key, value)?,
+            "fluid.fuel_injection" => fluid.fuel_injection = parse(key, value)?,
+            "fluid.burn_rate" => fluid.burn_rate = parse(key, value)?,
+            "fluid.heat_release" => fluid.heat_release = parse(key, value)?,
+            "fluid.ignition_temperature" => fluid.ignition_temperature = parse(key, value)?,
+            "fluid.source_temperature" => fluid.source_temperature = parse(key, value)?,
+            "fluid.cooling" => fluid.cooling = parse(key, value)?,
+            "fluid.cooling_map_coupling" => fluid.cooling_map_coupling = parse(key, value)?,
+            "obstacles.mask_path" => obstacles.mask_path = parse_optional(key, value)?,
+            "obstacles.brush_radius" => obstacles.brush_radius = parse(key, value)?,
+            "obstacles.color" => {
+                let channels = value
+                    .split(',')
+                    .map(|c| parse(key, c.trim()))
+                    .collect::<Result<Vec<u8>, _>>()?;
+                obstacles.color = channels.try_into().map_err(|_| invalid_value(key, value))?;
+            }
+            "fuel.enabled" => fuel.enabled = parse(key, value)?,
+            "fuel.bed_height" => fuel.bed_height = parse_at_most(key, value, MAX_CELLS)?,
+            "fuel.amount" => fuel.amount = parse(key, value)?,
+            "fuel.burn_rate" => fuel.burn_rate = parse(key, value)?,
+            "fuel.ignition_temperature" => fuel.ignition_temperature = parse(key, value)?,
+            "fuel.flame_temperature" => fuel.flame_temperature = parse(key, value)?,
+            "fuel.spread_chance" => fuel.spread_chance = parse(key, value)?,
+            "fuel.ignition_position" => fuel.ignition_position = parse(key, value)?,
+            "strip.length" => {
+                let length: usize = parse_at_most(key, value, MAX_CELLS)?;
+                if length == 0 {
+                    return Err(invalid_value(key, value));
+                }
+                strip.length = length;
+            }
+            "strip.layout" => {
+                strip.layout = StripLayout::from_name(value)
+                    .ok_or_else(|| invalid_value(key, value))?;
+            }
+            "strip.reversed" => strip.reversed = parse(key, value)?,
+            "strip.cooling" => strip.cooling = parse(key, value)?,
+            "strip.sparking" => strip.sparking = parse(key, value)?,
+            "strip.spark_zone" => strip.spark_zone = parse_at_most(key, value, MAX_CELLS)?,
+            "strip.spark_min" => strip.spark_min = parse(key, value)?,
+            "strip.spark_max" => strip.spark_max = parse(key, value)?,
+            "output.protocol" => {
+                output.protocol = OutputProtocol::from_name(value)
+                    .ok_or_else(|| invalid_value(key, value))?;
+            }
+            "output.address" => output.address = parse_optional(key, value)?,
+            "output.baud_rate" => output.baud_rate = parse(key, value)?,
+            "output.universe" => output.universe = parse(key, value)?,
+            "output.channels_per_universe" => {
+                let channels: usize = parse(key, value)?;
+                if !(1..=512).contains(&channels) {
+                    return Err(invalid_value(key, value));
+                }
+                output.channels_per_universe = channels;
+            }
+            "output.channel_offset" => output.channel_offset = parse_at_most(key, value, MAX_CELLS)?,
+            "output.map_path" => output.map_path = parse_optional(key, value)?,
+            "output.width" | "output.height" => {
+                let size: usize = parse_at_most(key, value, MAX_CELLS)?;
+                let other = if key == "output.width" { output.height } else { output.width };
+                if size * other.max(1) > MAX_CELLS {
+                    return Err(invalid_value(key, value));
+                }
+                if key == "output.width" {
+                    output.width = size;
+                } else {
+                    output.height = size;
+                }
+            }
+            "output.serpentine" => output.serpentine = parse(key, value)?,
+            "output.gamma" => {
+                let gamma: f64 = parse(key, value)?;
+                if gamma <= 0.0 {
+                    return Err(invalid_value(key, value));
+                }
+                output.gamma = gamma;
+            }
+            "output.brightness" => {
+                let brightness: f64 = parse(key, value)?;
+                if !(0.0..=1.0).contains(&brightness) {
+                    return Err(invalid_value(key, value));
+                }
+                output.brightness = brightness;
+            }
+            "output.fps" => {
+                let fps: f64 = parse(key, value)?;
+                if fps <= 0.0 {
+                    return Err(invalid_value(key, value));
+                }
+                output.fps = fps;
+            }
+            "audio.gain" => audio.gain = parse(key, value)?,
+            "audio.range" => {
+                let range: f64 = parse(key, value)?;
+                if range <= 0.0 {
+                    return Err(invalid_value(key, value));
+                }
+                audio.range = range;
+            }
+            "audio.attack" | "audio.release" => {
+                let seconds: f64 = parse(key, value)?;
+                if seconds < 0.0 {
+                    return Err(invalid_value(key, value));
+                }
+                if key == "audio.attack" {
+                    audio.attack = seconds;
+                } else {
+                    audio.release = seconds;
+                }
+            }
+            "timing.simulation_rate" => {
+                let rate: f64 = parse(key, value)?;
+                if rate <= 0.0 {
+                    return Err(invalid_value(key, value));
+                }
+                timing.simulation_rate = rate;
+            }
+            "timing.max_steps_per_frame" => {
+                timing.max_steps_per_frame = parse_at_most(key, value, MAX_STEPS_PER_FRAME)?;
+            }
+            "timing.interpolate" => timing.interpolate = parse(key, value)?,
+            _ => return Err(format!("unknown config {}", key)),
         }
+        Ok(())
+    }
+
+    /// Writes all values as `key = value` lines.
+    pub fn to_text(&self) -> String {
+        self.entries()
+            .iter()
+            .map(|(key, value)| format!("{} = {}\n", key, value))
+            .collect()
+    }
+
+    /// Applies `key = value` lines as written by [`FireConfigs::to_text`].
+    /// Empty lines and lines starting with `#` are skipped.
+    pub fn apply_text(&mut self, text: &str) -> Result<(), String> {
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("invalid config line {}", line))?;
+            self.set(key.trim(), value)?;
+        }
+        Ok(())
     }
 
     pub fn set_color_map_name(&mut self, color_map_name: String) {
         self.color_map_name = color_map_name;
     }
+
+    pub fn set_simulation_mode(&mut self, mode: SimulationMode) {
+        self.simulation_mode = mode;
+    }
+
+    pub fn set_fire_mask_mode(&mut self, mode: FireMaskMode) {
+        self.fire_mask_configs.mode = mode;
+    }
+}
+
+/// The model used to move and cool the heat.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimulationMode {
+    /// Cellular smoothing with a scrolling cooling map.
+    Classic,
+    /// Stable fluids solver with velocity, temperature and fuel fields.
+    Fluid,
 }
 
+impl SimulationMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SimulationMode::Classic => "classic",
+            SimulationMode::Fluid => "fluid",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "classic" => Some(SimulationMode::Classic),
+            "fluid" => Some(SimulationMode::Fluid),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            SimulationMode::Classic => SimulationMode::Fluid,
+            SimulationMode::Fluid => SimulationMode::Classic,
+        }
+    }
+}
+
+fn invalid_value(key: &str, value: &str) -> String {
+    format!("invalid value {} for {}", value, key)
+}
+
+fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| invalid_value(key, value))
+}
+
+/// Parses a size, count or iteration value, rejecting anything above `max` so
+/// that no configuration source can make the fire allocate or loop without
+/// bound.
+fn parse_at_most<T: std::str::FromStr + PartialOrd>(key: &str, value: &str, max: T) -> Result<T, String> {
+    let parsed: T = parse(key, value)?;
+    if parsed > max {
+        return Err(invalid_value(key, value));
+    }
+    Ok(parsed)
+}
+
+fn parse_optional<T: std::str::FromStr>(key: &str, value: &str) -> Result<Option<T>, String> {
+    match value {
+        "" | "none" => Ok(None),
+        _ => parse(key, value).map(Some),
+    }
+}
+
+#[derive(Clone)]
 pub struct CoolingMapConfigs {
     pub length_scale: f64,
     pub strength: f64,
 }
 
 impl CoolingMapConfigs {
+    #[allow(dead_code)]
     fn new(length_scale: f64, strength: f64) -> Self {
         Self {
             length_scale,
@@ -59,4 +463,412 @@ impl CoolingMapConfigs {
             strength: DEFAULT_COOLING_STRENGTH
         }
     }
-}
\ No newline at end of file
+}
+
+/// How the fire mask at the base of the fire evolves over time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FireMaskMode {
+    /// The mask is generated once and never changes.
+    Static,
+    /// A fraction of the base cells is re-randomised every frame.
+    Flicker,
+    /// The intensity along the base follows a scrolling noise function.
+    Noise,
+    /// A few hot spots slowly drift along the base.
+    Drift,
+}
+
+impl FireMaskMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FireMaskMode::Static => "static",
+            FireMaskMode::Flicker => "flicker",
+            FireMaskMode::Noise => "noise",
+            FireMaskMode::Drift => "drift",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "static" => Some(FireMaskMode::Static),
+            "flicker" => Some(FireMaskMode::Flicker),
+            "noise" => Some(FireMaskMode::Noise),
+            "drift" => Some(FireMaskMode::Drift),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            FireMaskMode::Static => FireMaskMode::Flicker,
+            FireMaskMode::Flicker => FireMaskMode::Noise,
+            FireMaskMode::Noise => FireMaskMode::Drift,
+            FireMaskMode::Drift => FireMaskMode::Static,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FireMaskConfigs {
+    pub mode: FireMaskMode,
+    /// Probability per frame that a base cell is re-randomised (flicker mode).
+    pub flicker_rate: f64,
+    /// Noise length scale along the base (noise and drift modes).
+    pub length_scale: f64,
+    /// Noise time increment per frame (noise and drift modes).
+    pub speed: f64,
+    pub hot_spot_count: usize,
+    /// Width of a hot spot in cells (drift mode).
+    pub hot_spot_width: f64,
+}
+
+impl FireMaskConfigs {
+    fn default() -> Self {
+        Self {
+            mode: FireMaskMode::Static,
+            flicker_rate: DEFAULT_FIRE_MASK_FLICKER_RATE,
+            length_scale: DEFAULT_FIRE_MASK_LENGTH_SCALE,
+            speed: DEFAULT_FIRE_MASK_SPEED,
+            hot_spot_count: DEFAULT_FIRE_MASK_HOT_SPOT_COUNT,
+            hot_spot_width: DEFAULT_FIRE_MASK_HOT_SPOT_WIDTH,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ForceConfigs {
+    /// Horizontal displacement in cells per frame, positive to the right.
+    pub wind: f64,
+    /// Amplitude of the gusts added on top of the global wind.
+    pub gust_strength: f64,
+    /// Noise length scale of the gusts along the height of the fire.
+    pub gust_length_scale: f64,
+    /// Noise time increment of the gusts per frame.
+    pub gust_speed: f64,
+    /// Upward displacement of the heat in cells per frame.
+    pub buoyancy: f64,
+}
+
+impl ForceConfigs {
+    fn default() -> Self {
+        Self {
+            wind: DEFAULT_WIND,
+            gust_strength: DEFAULT_GUST_STRENGTH,
+            gust_length_scale: DEFAULT_GUST_LENGTH_SCALE,
+            gust_speed: DEFAULT_GUST_SPEED,
+            buoyancy: DEFAULT_BUOYANCY,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FluidConfigs {
+    /// Number of heat buffer cells per fluid cell along each axis.
+    pub resolution_scale: usize,
+    pub time_step: f32,
+    /// Gauss-Seidel iterations of the diffusion and pressure solves.
+    pub iterations: usize,
+    pub viscosity: f32,
+    /// Upward acceleration per unit temperature.
+    pub buoyancy: f32,
+    /// Fraction of the velocity lost per time unit.
+    pub drag: f32,
+    /// Strength of the vorticity confinement.
+    pub vorticity: f32,
+    /// Fuel added per time unit by a fully lit base cell.
+    pub fuel_injection: f32,
+    /// Fuel burnt per time unit in a cell above the ignition temperature.
+    pub burn_rate: f32,
+    /// Temperature gained per unit of burnt fuel.
+    pub heat_release: f32,
+    pub ignition_temperature: f32,
+    /// Temperature of a fully lit base cell.
+    pub source_temperature: f32,
+    /// Fraction of the temperature lost per time unit.
+    pub cooling: f32,
+    /// Multiplier of the cooling map, which is applied on top of `cooling`.
+    pub cooling_map_coupling: f32,
+}
+
+impl FluidConfigs {
+    fn default() -> Self {
+        Self {
+            resolution_scale: DEFAULT_FLUID_RESOLUTION_SCALE,
+            time_step: DEFAULT_FLUID_TIME_STEP,
+            iterations: DEFAULT_FLUID_ITERATIONS,
+            viscosity: DEFAULT_FLUID_VISCOSITY,
+            buoyancy: DEFAULT_FLUID_BUOYANCY,
+            drag: DEFAULT_FLUID_DRAG,
+            vorticity: DEFAULT_FLUID_VORTICITY,
+            fuel_injection: DEFAULT_FLUID_FUEL_INJECTION,
+            burn_rate: DEFAULT_FLUID_BURN_RATE,
+            heat_release: DEFAULT_FLUID_HEAT_RELEASE,
+            ignition_temperature: DEFAULT_FLUID_IGNITION_TEMPERATURE,
+            source_temperature: DEFAULT_FLUID_SOURCE_TEMPERATURE,
+            cooling: DEFAULT_FLUID_COOLING,
+            cooling_map_coupling: DEFAULT_FLUID_COOLING_MAP_COUPLING,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ObstacleConfigs {
+    /// Image whose bright pixels mark solid cells.
+    pub mask_path: Option<String>,
+    /// Radius in cells of the brush used to draw obstacles with the mouse.
+    pub brush_radius: f32,
+    pub color: [u8; 3],
+}
+
+impl ObstacleConfigs {
+    fn default() -> Self {
+        Self {
+            mask_path: None,
+            brush_radius: DEFAULT_OBSTACLE_BRUSH_RADIUS,
+            color: DEFAULT_OBSTACLE_COLOR,
+        }
+    }
+}
+
+/// Fuel layer of the classic simulation. When enabled the burning fuel
+/// replaces the fire mask as the source of the fire.
+#[derive(Clone)]
+pub struct FuelConfigs {
+    pub enabled: bool,
+    /// Number of rows at the bottom that are covered with fuel.
+    pub bed_height: usize,
+    /// Maximum amount of fuel per cell.
+    pub amount: u8,
+    /// Fuel consumed per frame by a burning cell.
+    pub burn_rate: u8,
+    pub ignition_temperature: u8,
+    pub flame_temperature: u8,
+    /// Probability per frame that a hot neighbour ignites a cell.
+    pub spread_chance: f64,
+    /// Where the fuel is lit, as a fraction of the width.
+    pub ignition_position: f64,
+}
+
+impl FuelConfigs {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bed_height: DEFAULT_FUEL_BED_HEIGHT,
+            amount: DEFAULT_FUEL_AMOUNT,
+            burn_rate: DEFAULT_FUEL_BURN_RATE,
+            ignition_temperature: DEFAULT_FUEL_IGNITION_TEMPERATURE,
+            flame_temperature: DEFAULT_FUEL_FLAME_TEMPERATURE,
+            spread_chance: DEFAULT_FUEL_SPREAD_CHANCE,
+            ignition_position: DEFAULT_FUEL_IGNITION_POSITION,
+        }
+    }
+}
+
+/// How the fire is laid out along a strip of LEDs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StripLayout {
+    /// A single fire rising from the start of the strip.
+    Normal,
+    /// Two fires rising from both ends towards the centre.
+    Mirrored,
+    /// Two fires rising from the centre towards both ends.
+    CentreOut,
+}
+
+impl StripLayout {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StripLayout::Normal => "normal",
+            StripLayout::Mirrored => "mirrored",
+            StripLayout::CentreOut => "centre_out",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "normal" => Some(StripLayout::Normal),
+            "mirrored" => Some(StripLayout::Mirrored),
+            "centre_out" => Some(StripLayout::CentreOut),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            StripLayout::Normal => StripLayout::Mirrored,
+            StripLayout::Mirrored => StripLayout::CentreOut,
+            StripLayout::CentreOut => StripLayout::Normal,
+        }
+    }
+}
+
+/// One dimensional fire for addressable LED strips.
+#[derive(Clone)]
+pub struct StripConfigs {
+    /// Number of LEDs.
+    pub length: usize,
+    pub layout: StripLayout,
+    /// Reverse the order of the LEDs, for strips wired from the top.
+    pub reversed: bool,
+    /// Maximum heat lost per LED and step.
+    pub cooling: u8,
+    /// Probability per step of a new spark.
+    pub sparking: f64,
+    /// Number of LEDs at the base of the fire where sparks appear.
+    pub spark_zone: usize,
+    pub spark_min: u8,
+    pub spark_max: u8,
+}
+
+impl StripConfigs {
+    fn default() -> Self {
+        Self {
+            length: DEFAULT_STRIP_LENGTH,
+            layout: StripLayout::Normal,
+            reversed: false,
+            cooling: DEFAULT_STRIP_COOLING,
+            sparking: DEFAULT_STRIP_SPARKING,
+            spark_zone: DEFAULT_STRIP_SPARK_ZONE,
+            spark_min: DEFAULT_STRIP_SPARK_MIN,
+            spark_max: DEFAULT_STRIP_SPARK_MAX,
+        }
+    }
+}
+
+/// Protocol the colored frames are sent to LED controllers with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputProtocol {
+    None,
+    /// Open Pixel Control over TCP.
+    Opc,
+    /// E1.31 (streaming ACN) over UDP.
+    E131,
+    /// Art-Net DMX over UDP.
+    ArtNet,
+    /// Framed RGB over a serial port.
+    Serial,
+    /// Adalight over a serial port.
+    Adalight,
+}
+
+impl OutputProtocol {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputProtocol::None => "none",
+            OutputProtocol::Opc => "opc",
+            OutputProtocol::E131 => "e131",
+            OutputProtocol::ArtNet => "artnet",
+            OutputProtocol::Serial => "serial",
+            OutputProtocol::Adalight => "adalight",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "none" => Some(OutputProtocol::None),
+            "opc" => Some(OutputProtocol::Opc),
+            "e131" | "sacn" => Some(OutputProtocol::E131),
+            "artnet" => Some(OutputProtocol::ArtNet),
+            "serial" => Some(OutputProtocol::Serial),
+            "adalight" => Some(OutputProtocol::Adalight),
+            _ => None,
+        }
+    }
+}
+
+/// Output of the colored frames to LED matrices and strips over the network
+/// or a serial port.
+#[derive(Clone)]
+pub struct OutputConfigs {
+    pub protocol: OutputProtocol,
+    /// Host with an optional port, or the serial device. Without one E1.31
+    /// is multicast and Art-Net broadcast, OPC connects to the local server.
+    pub address: Option<String>,
+    /// Baud rate of the serial port.
+    pub baud_rate: u32,
+    /// First DMX universe, or the OPC channel.
+    pub universe: u16,
+    /// DMX channels used per universe. 510 keeps RGB pixels from being
+    /// split across two universes.
+    pub channels_per_universe: usize,
+    /// Channels skipped before the first pixel.
+    pub channel_offset: usize,
+    /// CSV or JSON layout with the position of every LED, sampled instead of
+    /// scaling the frame down to a matrix.
+    pub map_path: Option<String>,
+    /// Size of the LED matrix the frame is scaled down to. LED strips send
+    /// one pixel per LED instead.
+    pub width: usize,
+    pub height: usize,
+    /// Every other row of the matrix is wired right to left.
+    pub serpentine: bool,
+    pub gamma: f64,
+    /// Brightness limit between 0 and 1.
+    pub brightness: f64,
+    /// Frames sent per second, at most one per rendered frame.
+    pub fps: f64,
+}
+
+impl OutputConfigs {
+    fn default() -> Self {
+        Self {
+            protocol: OutputProtocol::None,
+            address: None,
+            baud_rate: DEFAULT_OUTPUT_BAUD_RATE,
+            universe: DEFAULT_OUTPUT_UNIVERSE,
+            channels_per_universe: DEFAULT_OUTPUT_CHANNELS_PER_UNIVERSE,
+            channel_offset: 0,
+            map_path: None,
+            width: DEFAULT_OUTPUT_WIDTH,
+            height: DEFAULT_OUTPUT_HEIGHT,
+            serpentine: false,
+            gamma: DEFAULT_OUTPUT_GAMMA,
+            brightness: DEFAULT_OUTPUT_BRIGHTNESS,
+            fps: DEFAULT_OUTPUT_FPS,
+        }
+    }
+}
+
+/// How the audio is turned into levels between 0 and 1 for the mapping.
+#[derive(Clone)]
+pub struct AudioConfigs {
+    /// Gain in dB applied before the levels are taken.
+    pub gain: f64,
+    /// Dynamic range in dB spread over the levels, so a full scale sine is 1
+    /// and `range` dB below it 0.
+    pub range: f64,
+    /// Seconds the levels take to rise and to fall, smoothing them.
+    pub attack: f64,
+    pub release: f64,
+}
+
+impl AudioConfigs {
+    fn default() -> Self {
+        Self {
+            gain: 0.0,
+            range: DEFAULT_AUDIO_RANGE,
+            attack: DEFAULT_AUDIO_ATTACK,
+            release: DEFAULT_AUDIO_RELEASE,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TimingConfigs {
+    /// Simulation steps per second, independent of the render frame rate.
+    pub simulation_rate: f64,
+    /// Cap on the steps taken in one render frame to catch up.
+    pub max_steps_per_frame: u32,
+    /// Blend the last two steps when rendering between them.
+    pub interpolate: bool,
+}
+
+impl TimingConfigs {
+    fn default() -> Self {
+        Self {
+            simulation_rate: DEFAULT_SIMULATION_RATE,
+            max_steps_per_frame: DEFAULT_MAX_STEPS_PER_FRAME,
+            interpolate: false,
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::sync::mpsc::{channel, Receiver};
+use crate::cli::AppOptions;
+use crate::configs::FireConfigs;
+use crate::http::{HttpServer, LiveStatus};
+use crate::midi::spawn_midi_input;
+use crate::osc::spawn_osc_server;
+
+/// A controller asking to set the config value `key`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigChange {
+    pub key: String,
+    pub value: String,
+}
+
+/// What a controller or the audio sets its config value to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControlTarget {
+    /// Spreads the controller values or levels linearly from `min` to `max`.
+    Range(f64, f64),
+    /// Splits the controller values or levels evenly between the choices.
+    Choices(Vec<String>),
+}
+
+impl ControlTarget {
+    /// Parses `MIN..MAX` or a comma separated list of choices.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        if let Some((min, max)) = spec.split_once("..") {
+            let parse = |bound: &str| {
+                bound.trim().parse::<f64>().map_err(|_| format!("invalid range {}", spec))
+            };
+            return Ok(ControlTarget::Range(parse(min)?, parse(max)?));
+        }
+        let choices: Vec<String> = spec.split(',').map(|choice| choice.trim().to_string()).collect();
+        if choices.iter().any(String::is_empty) {
+            return Err(format!("invalid choices {}", spec));
+        }
+        Ok(ControlTarget::Choices(choices))
+    }
+
+    /// The range or choices used when MIDI learning `key` without one.
+    pub fn default_for(key: &str) -> Option<Self> {
+        Some(match key {
+            "fill_percentage" => ControlTarget::Range(0.0, 100.0),
+            "cooling.strength" => ControlTarget::Range(0.0, 0.5),
+            "cooling.length_scale" => ControlTarget::Range(0.001, 0.1),
+            "forces.wind" => ControlTarget::Range(-2.0, 2.0),
+            "forces.buoyancy" => ControlTarget::Range(0.0, 3.0),
+            "color_map" => ControlTarget::Choices(
+                ["gray", "magma", "inferno", "plasma", "viridis"].map(String::from).to_vec(),
+            ),
+            _ => return None,
+        })
+    }
+
+    /// Config value for the level `level` between 0 and 1.
+    pub fn level(&self, level: f64) -> String {
+        let level = level.clamp(0.0, 1.0);
+        match self {
+            ControlTarget::Range(min, max) => (min + (max - min) * level).to_string(),
+            ControlTarget::Choices(choices) => {
+                let i = ((level * choices.len() as f64) as usize).min(choices.len() - 1);
+                choices[i].clone()
+            }
+        }
+    }
+
+    /// Config value for the MIDI controller value `value` between 0 and 127.
+    pub fn value(&self, value: u8) -> String {
+        let value = value.min(127);
+        match self {
+            ControlTarget::Range(min, max) => (min + (max - min) * f64::from(value) / 127.0).to_string(),
+            ControlTarget::Choices(choices) => {
+                let i = usize::from(value) * choices.len() / 128;
+                choices[i].clone()
+            }
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        match self {
+            ControlTarget::Range(min, max) => format!("{}..{}", min, max),
+            ControlTarget::Choices(choices) => choices.join(","),
+        }
+    }
+}
+
+/// A controller asking the render loop to do something other than changing
+/// a config.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Pause,
+    Resume,
+    /// Save a snapshot to the path.
+    SaveSnapshot(String),
+}
+
+/// Applies `change`. Integer configs also take fractional values, rounded,
+/// as controllers tend to send floats.
+pub fn apply_change(fire_configs: &mut FireConfigs, change: &ConfigChange) -> Result<(), String> {
+    fire_configs.set(&change.key, &change.value).or_else(|e| {
+        match change.value.trim().parse::<f64>() {
+            Ok(value) if value.fract() != 0.0 => {
+                fire_configs.set(&change.key, &(value.round() as i64).to_string()).map_err(|_| e)
+            }
+            _ => Err(e),
+        }
+    })
+}
+
+/// Collects the config changes and commands of the OSC, MIDI and HTTP
+/// inputs, which run on threads of their own, so the render loop applies them
+/// between steps. The render loop publishes its state back for the HTTP
+/// server.
+pub struct Controls {
+    changes: Receiver<ConfigChange>,
+    commands: Receiver<Command>,
+    http: Option<HttpServer>,
+}
+
+impl Controls {
+    /// Starts the inputs enabled in `options`.
+    pub fn start(options: &AppOptions) -> Result<Self, String> {
+        let (sender, changes) = channel();
+        let (command_sender, commands) = channel();
+        if let Some(port) = options.osc_port {
+            spawn_osc_server(options.osc_bind, port, sender.clone())?;
+        }
+        if let Some(device) = &options.midi_device {
+            spawn_midi_input(device, &options.midi_mapping, options.midi_learn.iter().cloned().collect(), sender.clone())?;
+        }
+        let http = match options.http_port {
+            // the strip has no snapshots
+            Some(port) => Some(HttpServer::start(
+                port,
+                sender,
+                command_sender,
+                (!options.strip).then(|| options.snapshot_path.clone()),
+            )?),
+            None => None,
+        };
+        Ok(Self { changes, commands, http })
+    }
+
+    /// Applies the changes received since the last call. Returns whether the
+    /// color map changed, so the caller can rebuild its table.
+    pub fn apply(&self, fire_configs: &mut FireConfigs) -> bool {
+        let color_map = fire_configs.color_map_name.clone();
+        for change in self.changes.try_iter() {
+            if let Err(e) = apply_change(fire_configs, &change) {
+                eprintln!("{}", e);
+            }
+        }
+        fire_configs.color_map_name != color_map
+    }
+
+    /// The commands received since the last call.
+    pub fn commands(&self) -> Vec<Command> {
+        self.commands.try_iter().collect()
+    }
+
+    pub fn serves_http(&self) -> bool {
+        self.http.is_some()
+    }
+
+    /// Shows the current configs and status to the HTTP clients.
+    pub fn publish(&self, fire_configs: &FireConfigs, status: LiveStatus) {
+        if let Some(http) = &self.http {
+            http.publish(fire_configs, status);
+        }
+    }
+
+    /// Whether an HTTP client waits for the next colored frame.
+    pub fn wants_frame(&self) -> bool {
+        self.http.as_ref().is_some_and(HttpServer::wants_frame)
+    }
+
+    pub fn publish_frame(&self, pixels: &[[u8; 4]], w: usize, h: usize) {
+        if let Some(http) = &self.http {
+            http.publish_frame(pixels, w, h);
+        }
+    }
+}
@@ -7,9 +7,21 @@ pub fn initialise_cooling_map(
     noise_function: &impl NoiseFn<f64, 2>,
     increment: f64,
     scale: f64,
+) -> VecDeque<u8> {
+    initialise_cooling_map_at(w, h, noise_function, increment, scale, 0.0)
+}
+
+/// Generates a cooling map whose rows start one increment below `ystart`.
+pub fn initialise_cooling_map_at(
+    w: usize,
+    h: usize,
+    noise_function: &impl NoiseFn<f64, 2>,
+    increment: f64,
+    scale: f64,
+    ystart: f64,
 ) -> VecDeque<u8> {
     let mut xoff: f64;
-    let mut yoff = 0.0;
+    let mut yoff = ystart;
     let mut cm_buf = VecDeque::with_capacity(w * h);
     for _y in 0..h {
         xoff = 0.0;
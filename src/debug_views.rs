@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+use macroquad::color::{colors, Color};
+use macroquad::math::vec2;
+use macroquad::shapes::{draw_rectangle, draw_rectangle_lines};
+use macroquad::texture::{draw_texture_ex, DrawTextureParams, Image, Texture2D};
+use crate::color_maps::ColorLut;
+use crate::configs::FireConfigs;
+use crate::cooling_maps::{initialise_cooling_map_at, update_cooling_map};
+use crate::simulation::FireSimulation;
+
+/// Picture in picture view of a buffer, created the first time it is shown.
+struct Tile {
+    image: Image,
+    texture: Texture2D,
+}
+
+impl Tile {
+    fn new(w: usize, h: usize) -> Self {
+        let image = Image::gen_image_color(w as u16, h as u16, colors::BLACK);
+        let texture = Texture2D::from_image(&image);
+        Self { image, texture }
+    }
+
+    fn update(&mut self, colors: impl Iterator<Item = [u8; 4]>) {
+        for (pixel, color) in self.image.get_image_data_mut().iter_mut().zip(colors) {
+            *pixel = color;
+        }
+        self.texture.update(&self.image);
+    }
+}
+
+/// Toggleable debug overlays. Buffers for a view are only created and kept up
+/// to date while it is shown, so hidden views cost nothing.
+pub struct DebugViews {
+    pub cooling_map: bool,
+    pub heat: bool,
+    pub fire_mask: bool,
+    pub difference: bool,
+    pub histogram: bool,
+    /// Cooling map at full strength, for better contrast.
+    cooling_map_buf: VecDeque<u8>,
+    cooling_map_tile: Option<Tile>,
+    heat_tile: Option<Tile>,
+    fire_mask_tile: Option<Tile>,
+    difference_tile: Option<Tile>,
+}
+
+impl DebugViews {
+    pub fn new() -> Self {
+        Self {
+            cooling_map: false,
+            heat: false,
+            fire_mask: false,
+            difference: false,
+            histogram: false,
+            cooling_map_buf: VecDeque::new(),
+            cooling_map_tile: None,
+            heat_tile: None,
+            fire_mask_tile: None,
+            difference_tile: None,
+        }
+    }
+
+    pub fn toggle_cooling_map(&mut self, simulation: &FireSimulation, fire_configs: &FireConfigs) {
+        self.cooling_map = !self.cooling_map;
+        if self.cooling_map {
+            self.sync_cooling_map(simulation, fire_configs);
+        }
+    }
+
+    /// Regenerates the full strength cooling map to match the simulation,
+    /// e.g. after a snapshot was loaded.
+    pub fn sync_cooling_map(&mut self, simulation: &FireSimulation, fire_configs: &FireConfigs) {
+        if !self.cooling_map {
+            return;
+        }
+        // the last row pushed by update_cooling_map sits at ystart + (h - 1) increments
+        let length_scale = fire_configs.cooling_map_configs.length_scale;
+        self.cooling_map_buf = initialise_cooling_map_at(
+            simulation.w,
+            simulation.h,
+            &simulation.noise_function,
+            length_scale,
+            1.0,
+            simulation.ystart - length_scale,
+        );
+    }
+
+    /// Must be called before every simulation step, the full strength cooling
+    /// map follows the one of the simulation.
+    pub fn before_step(&mut self, simulation: &FireSimulation, fire_configs: &FireConfigs) {
+        if !self.cooling_map {
+            return;
+        }
+        update_cooling_map(
+            &mut self.cooling_map_buf,
+            simulation.w,
+            simulation.h,
+            &simulation.noise_function,
+            fire_configs.cooling_map_configs.length_scale,
+            1.0,
+            simulation.ystart,
+        );
+    }
+
+    /// Draws the enabled views, image views as tiles down the right edge and
+    /// the histogram in the bottom left corner. `previous` holds the heat
+    /// before the last step.
+    pub fn draw(
+        &mut self,
+        simulation: &FireSimulation,
+        previous: &[u8],
+        color_lut: &ColorLut,
+    ) {
+        let (w, h) = (simulation.w, simulation.h);
+        let tile_w = w as f32 / 4.0;
+        let tile_h = h as f32 / 4.0;
+        let mut slot = 0;
+        let mut draw_tile = |tile: &Tile| {
+            let x = w as f32 - tile_w;
+            let y = slot as f32 * tile_h;
+            draw_texture_ex(
+                &tile.texture,
+                x,
+                y,
+                colors::WHITE,
+                DrawTextureParams {
+                    dest_size: Some(vec2(tile_w, tile_h)),
+                    ..Default::default()
+                },
+            );
+            draw_rectangle_lines(x, y, tile_w, tile_h, 1.0, colors::WHITE);
+            slot += 1;
+        };
+
+        if self.cooling_map {
+            let tile = self.cooling_map_tile.get_or_insert_with(|| Tile::new(w, h));
+            tile.update(self.cooling_map_buf.iter().map(|&v| color_lut.color(v)));
+            draw_tile(tile);
+        }
+        if self.heat {
+            let tile = self.heat_tile.get_or_insert_with(|| Tile::new(w, h));
+            tile.update(simulation.buf.iter().map(|&v| [v, v, v, 255]));
+            draw_tile(tile);
+        }
+        if self.fire_mask {
+            let nrow = simulation.fire_mask.len() / w;
+            let tile = self.fire_mask_tile.get_or_insert_with(|| Tile::new(w, nrow));
+            tile.update(simulation.fire_mask.iter().map(|&v| [v, v, v, 255]));
+            draw_tile(tile);
+        }
+        if self.difference {
+            // heat gained in red, heat lost in blue, amplified for visibility
+            let tile = self.difference_tile.get_or_insert_with(|| Tile::new(w, h));
+            tile.update(simulation.buf.iter().zip(previous).map(|(&c, &p)| {
+                let diff = i16::from(c) - i16::from(p);
+                let amplified = (diff.unsigned_abs() * 4).min(255) as u8;
+                if diff >= 0 { [amplified, 0, 0, 255] } else { [0, 0, amplified, 255] }
+            }));
+            draw_tile(tile);
+        }
+        if self.histogram {
+            draw_histogram(&simulation.buf, h as f32);
+        }
+    }
+}
+
+/// Draws a 256 bin histogram of the heat with logarithmic counts.
+fn draw_histogram(buf: &[u8], screen_h: f32) {
+    let mut bins = [0u32; 256];
+    for &v in buf {
+        bins[v as usize] += 1;
+    }
+    let max = (bins.iter().copied().max().unwrap_or(1) as f32).ln_1p();
+    let (x0, height) = (4.0, 80.0);
+    let y0 = screen_h - 4.0 - height;
+    draw_rectangle(x0, y0, 256.0, height, Color::new(0.0, 0.0, 0.0, 0.6));
+    for (i, &count) in bins.iter().enumerate() {
+        let bar = (count as f32).ln_1p() / max * height;
+        draw_rectangle(x0 + i as f32, y0 + height - bar, 1.0, bar, colors::ORANGE);
+    }
+    draw_rectangle_lines(x0, y0, 256.0, height, 1.0, colors::WHITE);
+}
@@ -4,4 +4,85 @@ pub const DEFAULT_WINDOW_HEIGHT: i32 = 360;
 pub const DEFAULT_FILL_PERCENTAGE: u8 = 95;
 pub const DEFAULT_FIRE_BASE_HEIGHT: usize = 20;
 pub const DEFAULT_COOLING_LENGTH_SCALE: f64 = 0.02;
-pub const DEFAULT_COOLING_STRENGTH: f64 = 0.10;
\ No newline at end of file
+pub const DEFAULT_COOLING_STRENGTH: f64 = 0.10;
+
+pub const DEFAULT_FIRE_MASK_FLICKER_RATE: f64 = 0.05;
+pub const DEFAULT_FIRE_MASK_LENGTH_SCALE: f64 = 0.02;
+pub const DEFAULT_FIRE_MASK_SPEED: f64 = 0.01;
+pub const DEFAULT_FIRE_MASK_HOT_SPOT_COUNT: usize = 4;
+pub const DEFAULT_FIRE_MASK_HOT_SPOT_WIDTH: f64 = 60.0;
+
+pub const DEFAULT_WIND: f64 = 0.0;
+pub const DEFAULT_GUST_STRENGTH: f64 = 0.0;
+pub const DEFAULT_GUST_LENGTH_SCALE: f64 = 0.01;
+pub const DEFAULT_GUST_SPEED: f64 = 0.01;
+pub const DEFAULT_BUOYANCY: f64 = 1.0;
+
+pub const DEFAULT_FLUID_RESOLUTION_SCALE: usize = 4;
+pub const DEFAULT_FLUID_TIME_STEP: f32 = 0.5;
+pub const DEFAULT_FLUID_ITERATIONS: usize = 20;
+pub const DEFAULT_FLUID_VISCOSITY: f32 = 0.0;
+pub const DEFAULT_FLUID_BUOYANCY: f32 = 1.0;
+pub const DEFAULT_FLUID_DRAG: f32 = 0.2;
+pub const DEFAULT_FLUID_VORTICITY: f32 = 0.3;
+pub const DEFAULT_FLUID_FUEL_INJECTION: f32 = 1.0;
+pub const DEFAULT_FLUID_BURN_RATE: f32 = 0.5;
+pub const DEFAULT_FLUID_HEAT_RELEASE: f32 = 1.0;
+pub const DEFAULT_FLUID_IGNITION_TEMPERATURE: f32 = 0.3;
+pub const DEFAULT_FLUID_SOURCE_TEMPERATURE: f32 = 1.0;
+pub const DEFAULT_FLUID_COOLING: f32 = 0.2;
+pub const DEFAULT_FLUID_COOLING_MAP_COUPLING: f32 = 4.0;
+
+pub const DEFAULT_OBSTACLE_BRUSH_RADIUS: f32 = 6.0;
+pub const DEFAULT_OBSTACLE_COLOR: [u8; 3] = [70, 90, 110];
+
+pub const DEFAULT_FUEL_BED_HEIGHT: usize = 4;
+pub const DEFAULT_FUEL_AMOUNT: u8 = 250;
+pub const DEFAULT_FUEL_BURN_RATE: u8 = 2;
+pub const DEFAULT_FUEL_IGNITION_TEMPERATURE: u8 = 100;
+pub const DEFAULT_FUEL_FLAME_TEMPERATURE: u8 = 255;
+pub const DEFAULT_FUEL_SPREAD_CHANCE: f64 = 0.3;
+pub const DEFAULT_FUEL_IGNITION_POSITION: f64 = 0.0;
+
+pub const DEFAULT_STRIP_LENGTH: usize = 60;
+pub const DEFAULT_STRIP_COOLING: u8 = 10;
+pub const DEFAULT_STRIP_SPARKING: f64 = 0.5;
+pub const DEFAULT_STRIP_SPARK_ZONE: usize = 7;
+pub const DEFAULT_STRIP_SPARK_MIN: u8 = 160;
+pub const DEFAULT_STRIP_SPARK_MAX: u8 = 255;
+
+pub const DEFAULT_OUTPUT_BAUD_RATE: u32 = 115200;
+pub const DEFAULT_OUTPUT_UNIVERSE: u16 = 1;
+pub const DEFAULT_OUTPUT_CHANNELS_PER_UNIVERSE: usize = 510;
+pub const DEFAULT_OUTPUT_WIDTH: usize = 32;
+pub const DEFAULT_OUTPUT_HEIGHT: usize = 18;
+pub const DEFAULT_OUTPUT_GAMMA: f64 = 2.2;
+pub const DEFAULT_OUTPUT_BRIGHTNESS: f64 = 1.0;
+pub const DEFAULT_OUTPUT_FPS: f64 = 40.0;
+
+pub const DEFAULT_AUDIO_RANGE: f64 = 60.0;
+pub const DEFAULT_AUDIO_ATTACK: f64 = 0.01;
+pub const DEFAULT_AUDIO_RELEASE: f64 = 0.25;
+pub const DEFAULT_AUDIO_SAMPLE_RATE: u32 = 44100;
+pub const DEFAULT_AUDIO_CHANNELS: u16 = 2;
+
+pub const DEFAULT_SNAPSHOT_PATH: &str = "rusty-fire.snapshot";
+pub const DEFAULT_SCREENSHOT_DIR: &str = ".";
+pub const DEFAULT_MIDI_MAPPING_PATH: &str = "rusty-fire.midimap";
+
+pub const DEFAULT_PRE_ROLL_WINDOW: u64 = 100;
+pub const DEFAULT_PRE_ROLL_TOLERANCE: f64 = 0.03;
+pub const DEFAULT_PRE_ROLL_MAX_FRAMES: u64 = 3000;
+
+pub const DEFAULT_SIMULATION_RATE: f64 = 60.0;
+pub const DEFAULT_MAX_STEPS_PER_FRAME: u32 = 4;
+
+pub const DEFAULT_EXPORT_CODEC: &str = "libx264";
+pub const DEFAULT_EXPORT_FPS: f64 = 30.0;
+pub const DEFAULT_EXPORT_DURATION: f64 = 10.0;
+
+pub const DEFAULT_TERMINAL_FPS: f64 = 30.0;
+
+pub const DEFAULT_HTTP_STREAM_FPS: f64 = 15.0;
+
+pub const DEFAULT_OSC_BIND_ADDRESS: std::net::IpAddr = std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST);
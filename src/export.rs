@@ -0,0 +1,122 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use crate::audio::AudioReactive;
+use crate::color_maps::ColorLut;
+use crate::configs::FireConfigs;
+use crate::defaults::{DEFAULT_EXPORT_CODEC, DEFAULT_EXPORT_DURATION, DEFAULT_EXPORT_FPS};
+use crate::simulation::FireSimulation;
+use crate::timing::{interpolate, FixedTimestep};
+
+/// Settings of a video export.
+pub struct ExportOptions {
+    /// Any encoder ffmpeg knows, e.g. libx264, libx265 or libvpx-vp9.
+    pub codec: String,
+    pub fps: f64,
+    /// Size of the video, the frames are scaled by ffmpeg. Defaults to the grid size.
+    pub size: Option<(usize, usize)>,
+    /// Length of the video in seconds.
+    pub duration: f64,
+    /// Seconds at the end of the video that cross-fade into its start, so it
+    /// loops seamlessly. No cross-fade if zero.
+    pub crossfade: f64,
+}
+
+impl ExportOptions {
+    pub fn default() -> Self {
+        Self {
+            codec: String::from(DEFAULT_EXPORT_CODEC),
+            fps: DEFAULT_EXPORT_FPS,
+            size: None,
+            duration: DEFAULT_EXPORT_DURATION,
+            crossfade: 0.0,
+        }
+    }
+}
+
+/// Renders the simulation as fast as possible and streams the frames as raw
+/// RGBA to an ffmpeg process that encodes them to `path`. The video runs in
+/// simulation time, independent of how long the rendering takes, and so does
+/// the audio modulating `fire_configs`.
+pub fn export_video(
+    path: &str,
+    simulation: &mut FireSimulation,
+    fire_configs: &mut FireConfigs,
+    color_lut: &ColorLut,
+    mut audio: Option<&mut AudioReactive>,
+    options: &ExportOptions,
+) -> Result<(), String> {
+    let (w, h) = (simulation.w, simulation.h);
+    let frame_count = (options.duration * options.fps).round() as usize;
+    let crossfade_count = (options.crossfade * options.fps).round() as usize;
+    if frame_count == 0 {
+        return Err(String::from("the export duration is shorter than a frame"));
+    }
+    if crossfade_count > frame_count {
+        return Err(String::from("the cross-fade is longer than the export"));
+    }
+
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+        .args(["-s", &format!("{}x{}", w, h), "-r", &options.fps.to_string(), "-i", "-"]);
+    if let Some((video_w, video_h)) = options.size {
+        command.args(["-vf", &format!("scale={}:{}", video_w, video_h)]);
+    }
+    command
+        .args(["-c:v", &options.codec, "-pix_fmt", "yuv420p", path])
+        .stdin(Stdio::piped());
+    let mut ffmpeg = command.spawn().map_err(|e| format!("could not start ffmpeg: {}", e))?;
+    let mut stdin = ffmpeg.stdin.take().expect("stdin of ffmpeg is piped");
+
+    let [r, g, b] = fire_configs.obstacle_configs.color;
+    let obstacle_color = [r, g, b, 255];
+    let mut timestep = FixedTimestep::new(fire_configs.timing_configs.simulation_rate, u32::MAX);
+    let mut previous = simulation.buf.clone();
+    let mut interpolated = simulation.buf.clone();
+    let mut pixels = vec![[0u8; 4]; w * h];
+    // the first frames are held back and blended into the last ones
+    let mut loop_start = Vec::with_capacity(crossfade_count);
+
+    let mut result = Ok(());
+    for frame in 0..frame_count + crossfade_count {
+        for _ in 0..timestep.advance(1.0 / options.fps) {
+            if let Some(audio) = audio.as_deref_mut() {
+                audio.step(fire_configs);
+            }
+            previous.copy_from_slice(&simulation.buf);
+            simulation.step(fire_configs);
+        }
+        let heat = if fire_configs.timing_configs.interpolate {
+            interpolate(&previous, &simulation.buf, timestep.alpha(), &mut interpolated);
+            &interpolated
+        } else {
+            &simulation.buf
+        };
+        color_lut.apply_masked(heat, &simulation.obstacles, obstacle_color, &mut pixels);
+
+        if frame < crossfade_count {
+            loop_start.push(pixels.clone());
+            continue;
+        }
+        if let Some(fade) = frame.checked_sub(frame_count) {
+            // fade towards the frame preceding the start of the video
+            let weight = (fade + 1) as f32 / (crossfade_count + 1) as f32;
+            for (pixel, start) in pixels.iter_mut().zip(&loop_start[fade]) {
+                for (channel, &target) in pixel.iter_mut().zip(start) {
+                    *channel = (f32::from(*channel) * (1.0 - weight) + f32::from(target) * weight).round() as u8;
+                }
+            }
+        }
+        if let Err(e) = stdin.write_all(pixels.as_flattened()) {
+            result = Err(format!("could not write to ffmpeg: {}", e));
+            break;
+        }
+    }
+    drop(stdin);
+
+    let status = ffmpeg.wait().map_err(|e| format!("ffmpeg failed: {}", e))?;
+    if !status.success() {
+        return Err(format!("ffmpeg failed with {}", status));
+    }
+    result
+}
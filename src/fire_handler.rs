@@ -4,19 +4,26 @@ pub fn seed_fire(
     buf: &mut [u8],
     w: usize,
     h: usize,
-    fire_mask: &[bool],
+    fire_mask: &[u8],
+    obstacles: &[bool],
 ) {
     let nrow = fire_mask.len() / w;
-    // add new fire points
+    // add new fire points, unlit cells are left to cool down
     for y in 0..nrow {
         for x in 0..w {
-            if fire_mask[x + y * w] {
-                buf[x + (h - y - 1) * w] = 255;
+            let i = x + (h - y - 1) * w;
+            if obstacles[i] {
+                buf[i] = 0;
+            } else if fire_mask[x + y * w] > 0 {
+                buf[i] = fire_mask[x + y * w];
             }
         }
     }
 }
 
+/// Obstacle cells hold no heat and act as reflecting boundaries: a neighbour
+/// inside an obstacle contributes the value of the cell itself to the average.
+#[allow(clippy::too_many_arguments)]
 pub fn smooth_and_cool(
     original: &[u8],
     new: &mut [u8],
@@ -25,20 +32,27 @@ pub fn smooth_and_cool(
     yshift: usize,
     cooling_map: &VecDeque<u8>,
     fire_height: usize,
-) -> () {
+    obstacles: &[bool],
+) {
     for x in 1..(w - 1) {
         for y in 1..(h - 1) {
             if y < yshift {
                 continue;
             }
+            if obstacles[x + (y - yshift) * w] {
+                new[x + (y - yshift) * w] = 0;
+                continue;
+            }
+            let centre = original[x + y * w];
+            let neighbour = |i: usize| u16::from(if obstacles[i] { centre } else { original[i] });
             let mut new_val = u8::try_from((
-                u16::from(original[x + (y - 1) * w])
-                    + u16::from(original[x + (y + 1) * w])
-                    + u16::from(original[(x - 1) + y * w])
-                    + u16::from(original[(x + 1) + y * w])
+                neighbour(x + (y - 1) * w)
+                    + neighbour(x + (y + 1) * w)
+                    + neighbour((x - 1) + y * w)
+                    + neighbour((x + 1) + y * w)
                 // + u16::from(original[x + y * w])
             ) / 4).unwrap();
-            if y < (h - fire_height) {
+            if y < h.saturating_sub(fire_height) {
                 let cooling_val = *cooling_map.get(x + y * w).unwrap();
                 if new_val > cooling_val {
                     new_val -= cooling_val;
@@ -50,4 +64,56 @@ pub fn smooth_and_cool(
             new[x + (y - yshift) * w] = new_val;
         }
     }
-}
\ No newline at end of file
+}
+
+/// Moves the heat in `original` along the wind field using semi-Lagrangian
+/// sampling. Each destination cell is traced back to where its heat came from
+/// (`wind[y]` cells horizontally, `buoyancy` cells upwards) and the source is
+/// sampled with bilinear interpolation, which allows fractional displacements.
+/// Obstacle cells are left out of the interpolation, so no heat is carried
+/// through them.
+pub fn advect(
+    original: &[u8],
+    new: &mut [u8],
+    w: usize,
+    h: usize,
+    wind: &[f64],
+    buoyancy: f64,
+    obstacles: &[bool],
+) {
+    let max_x = (w - 1) as f64;
+    let max_y = (h - 1) as f64;
+    for y in 0..h {
+        let sy = (y as f64 + buoyancy).clamp(0.0, max_y);
+        let y0 = sy.floor() as usize;
+        let y1 = (y0 + 1).min(h - 1);
+        let fy = sy - y0 as f64;
+        for x in 0..w {
+            if obstacles[x + y * w] {
+                new[x + y * w] = 0;
+                continue;
+            }
+            let sx = (x as f64 - wind[y]).clamp(0.0, max_x);
+            let x0 = sx.floor() as usize;
+            let x1 = (x0 + 1).min(w - 1);
+            let fx = sx - x0 as f64;
+
+            let mut heat: f64 = 0.0;
+            let mut weight: f64 = 0.0;
+            for (i, wgt) in [
+                (x0 + y0 * w, (1.0 - fx) * (1.0 - fy)),
+                (x1 + y0 * w, fx * (1.0 - fy)),
+                (x0 + y1 * w, (1.0 - fx) * fy),
+                (x1 + y1 * w, fx * fy),
+            ] {
+                if !obstacles[i] {
+                    heat += f64::from(original[i]) * wgt;
+                    weight += wgt;
+                }
+            }
+            // renormalise over the open corners, but not beyond twice their
+            // weight so that cells mostly hidden behind an obstacle stay dark
+            new[x + y * w] = (heat / weight.max(0.5)).round().min(255.0) as u8;
+        }
+    }
+}
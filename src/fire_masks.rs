@@ -0,0 +1,82 @@
+use noise::NoiseFn;
+use crate::configs::{FireMaskConfigs, FireMaskMode};
+use crate::rng::Rng;
+
+pub fn initialise_fire_base(w: usize, fill_percentage: u8, rng: &mut Rng) -> Vec<bool> {
+    (0..w).map(|_| rng.gen_range(0, 99) < fill_percentage).collect()
+}
+
+pub fn initialise_fire_mask(fire_base: &[bool], nrow: usize) -> Vec<u8> {
+    let mut fire_mask = Vec::with_capacity(nrow * fire_base.len());
+    for _y in 0..nrow {
+        fire_mask.extend(fire_base.iter().map(|&lit| if lit { 255 } else { 0 }));
+    }
+    fire_mask
+}
+
+pub fn update_fire_mask(
+    fire_mask: &mut [u8],
+    fire_base: &mut [bool],
+    configs: &FireMaskConfigs,
+    fill_percentage: u8,
+    noise_function: &impl NoiseFn<f64, 2>,
+    t: f64,
+    rng: &mut Rng,
+) {
+    let w = fire_base.len();
+    match configs.mode {
+        FireMaskMode::Static => {
+            // an animated mode may have left a partial mask behind, restore
+            // the full one once when switching back
+            if fire_mask[..w].iter().zip(fire_base.iter()).all(|(&v, &lit)| v == if lit { 255 } else { 0 }) {
+                return;
+            }
+            for x in 0..w {
+                fire_mask[x] = if fire_base[x] { 255 } else { 0 };
+            }
+        }
+        FireMaskMode::Flicker => {
+            for lit in fire_base.iter_mut() {
+                if rng.gen_f64() < configs.flicker_rate {
+                    *lit = rng.gen_range(0, 99) < fill_percentage;
+                }
+            }
+            for x in 0..w {
+                fire_mask[x] = if fire_base[x] { 255 } else { 0 };
+            }
+        }
+        FireMaskMode::Noise => {
+            for x in 0..w {
+                let n = noise_function.get([x as f64 * configs.length_scale, t]);
+                let intensity = (n * 0.5 + 0.5).clamp(0.0, 1.0);
+                fire_mask[x] = if fire_base[x] { (intensity * 255.0).round() as u8 } else { 0 };
+            }
+        }
+        FireMaskMode::Drift => {
+            // hot spot centres wander along the base following the noise function,
+            // each spot sampling it at a different, well separated offset
+            let centres: Vec<f64> = (0..configs.hot_spot_count)
+                .map(|i| {
+                    let n = noise_function.get([i as f64 * 7.3 + 0.5, t]);
+                    (n * 0.5 + 0.5).clamp(0.0, 1.0) * w as f64
+                })
+                .collect();
+            for x in 0..w {
+                let intensity = centres
+                    .iter()
+                    .map(|&c| {
+                        let d = (x as f64 - c) / configs.hot_spot_width;
+                        (-d * d).exp()
+                    })
+                    .fold(0.0, f64::max);
+                fire_mask[x] = if fire_base[x] { (intensity * 255.0).round() as u8 } else { 0 };
+            }
+        }
+    }
+
+    // all rows of the mask share the first one
+    let (first, rest) = fire_mask.split_at_mut(w);
+    for row in rest.chunks_mut(w) {
+        row.copy_from_slice(first);
+    }
+}
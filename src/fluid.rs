@@ -0,0 +1,296 @@
+use crate::configs::FluidConfigs;
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
+
+/// Stable fluids solver after Jos Stam, "Real-Time Fluid Dynamics for Games".
+///
+/// The grid has `nx * ny` interior cells surrounded by a one cell wide
+/// boundary. `y` grows downwards like in the heat buffer, so buoyancy pushes
+/// towards negative `v`. Velocities are measured in cells per time unit.
+pub struct FluidSolver {
+    pub nx: usize,
+    pub ny: usize,
+    u: Vec<f32>,
+    v: Vec<f32>,
+    u_prev: Vec<f32>,
+    v_prev: Vec<f32>,
+    pub temperature: Vec<f32>,
+    temperature_prev: Vec<f32>,
+    pub fuel: Vec<f32>,
+    fuel_prev: Vec<f32>,
+    curl: Vec<f32>,
+}
+
+impl FluidSolver {
+    pub fn new(nx: usize, ny: usize) -> Self {
+        let size = (nx + 2) * (ny + 2);
+        Self {
+            nx,
+            ny,
+            u: vec![0.0; size],
+            v: vec![0.0; size],
+            u_prev: vec![0.0; size],
+            v_prev: vec![0.0; size],
+            temperature: vec![0.0; size],
+            temperature_prev: vec![0.0; size],
+            fuel: vec![0.0; size],
+            fuel_prev: vec![0.0; size],
+            curl: vec![0.0; size],
+        }
+    }
+
+    pub fn write_state(&self, writer: &mut SnapshotWriter) {
+        writer.write_u64(self.nx as u64);
+        writer.write_u64(self.ny as u64);
+        writer.write_f32s(&self.u);
+        writer.write_f32s(&self.v);
+        writer.write_f32s(&self.temperature);
+        writer.write_f32s(&self.fuel);
+    }
+
+    pub fn read_state(reader: &mut SnapshotReader) -> Result<Self, String> {
+        let nx = reader.read_len()?;
+        let ny = reader.read_len()?;
+        // the velocity, temperature and fuel are stored as f32 per cell
+        let cells = nx.checked_add(2).zip(ny.checked_add(2)).and_then(|(x, y)| x.checked_mul(y));
+        reader.check_cells(cells, 16)?;
+        let mut fluid = Self::new(nx, ny);
+        let size = fluid.u.len();
+        for field in [&mut fluid.u, &mut fluid.v, &mut fluid.temperature, &mut fluid.fuel] {
+            *field = reader.read_f32s()?;
+            if field.len() != size {
+                return Err(String::from("snapshot is corrupt"));
+            }
+        }
+        Ok(fluid)
+    }
+
+    #[inline]
+    pub fn idx(&self, x: usize, y: usize) -> usize {
+        x + y * (self.nx + 2)
+    }
+
+    /// Adds fuel and heat to the bottom row of interior cells. `source` holds
+    /// one intensity in `[0, 1]` per interior column.
+    pub fn inject(&mut self, source: &[f32], configs: &FluidConfigs) {
+        let dt = configs.time_step;
+        for (i, &s) in source.iter().enumerate().take(self.nx) {
+            let id = self.idx(i + 1, self.ny);
+            self.fuel[id] += s * configs.fuel_injection * dt;
+            self.temperature[id] = self.temperature[id].max(s * configs.source_temperature);
+        }
+    }
+
+    /// Applies a horizontal force per interior row, e.g. the wind.
+    pub fn add_horizontal_force(&mut self, force: &[f32], dt: f32) {
+        for y in 1..=self.ny {
+            let f = force[y - 1];
+            for x in 1..=self.nx {
+                let id = self.idx(x, y);
+                self.u[id] += f * dt;
+            }
+        }
+    }
+
+    /// Removes heat per interior cell, `cooling` is laid out row by row.
+    pub fn cool(&mut self, cooling: &[f32], dt: f32) {
+        for y in 1..=self.ny {
+            for x in 1..=self.nx {
+                let id = self.idx(x, y);
+                let c = cooling[(x - 1) + (y - 1) * self.nx];
+                self.temperature[id] = (self.temperature[id] - c * dt).max(0.0);
+            }
+        }
+    }
+
+    /// Removes all gas and motion from solid cells, `solid` is laid out like
+    /// the cooling of [`FluidSolver::cool`].
+    pub fn clear_solid(&mut self, solid: &[bool]) {
+        for y in 1..=self.ny {
+            for x in 1..=self.nx {
+                if solid[(x - 1) + (y - 1) * self.nx] {
+                    let id = self.idx(x, y);
+                    self.u[id] = 0.0;
+                    self.v[id] = 0.0;
+                    self.temperature[id] = 0.0;
+                    self.fuel[id] = 0.0;
+                }
+            }
+        }
+    }
+
+    pub fn step(&mut self, configs: &FluidConfigs) {
+        let dt = configs.time_step;
+        self.burn(configs);
+        self.add_buoyancy(configs);
+        self.confine_vorticity(configs);
+        self.velocity_step(configs);
+        self.scalar_step(configs);
+
+        // radiative cooling
+        let decay = (1.0 - configs.cooling * dt).max(0.0);
+        for t in self.temperature.iter_mut() {
+            *t *= decay;
+        }
+    }
+
+    /// Burns fuel in cells that are hot enough and releases heat.
+    fn burn(&mut self, configs: &FluidConfigs) {
+        let dt = configs.time_step;
+        for (fuel, temperature) in self.fuel.iter_mut().zip(self.temperature.iter_mut()) {
+            if *fuel > 0.0 && *temperature >= configs.ignition_temperature {
+                let burnt = fuel.min(configs.burn_rate * dt);
+                *fuel -= burnt;
+                *temperature += burnt * configs.heat_release;
+            }
+        }
+    }
+
+    /// Lifts hot gas and damps the velocity, without the drag the open
+    /// domain would keep accelerating forever.
+    fn add_buoyancy(&mut self, configs: &FluidConfigs) {
+        let dt = configs.time_step;
+        let damping = (1.0 - configs.drag * dt).max(0.0);
+        for (v, &t) in self.v.iter_mut().zip(self.temperature.iter()) {
+            *v = (*v - configs.buoyancy * t * dt) * damping;
+        }
+        for u in self.u.iter_mut() {
+            *u *= damping;
+        }
+    }
+
+    /// Re-injects the small scale swirls that numerical dissipation removes.
+    fn confine_vorticity(&mut self, configs: &FluidConfigs) {
+        if configs.vorticity <= 0.0 {
+            return;
+        }
+        let (nx, ny) = (self.nx, self.ny);
+        for y in 1..=ny {
+            for x in 1..=nx {
+                let dv_dx = 0.5 * (self.v[self.idx(x + 1, y)] - self.v[self.idx(x - 1, y)]);
+                let du_dy = 0.5 * (self.u[self.idx(x, y + 1)] - self.u[self.idx(x, y - 1)]);
+                let id = self.idx(x, y);
+                self.curl[id] = dv_dx - du_dy;
+            }
+        }
+        let dt = configs.time_step;
+        for y in 2..ny {
+            for x in 2..nx {
+                let id = self.idx(x, y);
+                let nx_ = 0.5 * (self.curl[id + 1].abs() - self.curl[id - 1].abs());
+                let ny_ = 0.5 * (self.curl[self.idx(x, y + 1)].abs() - self.curl[self.idx(x, y - 1)].abs());
+                let len = (nx_ * nx_ + ny_ * ny_).sqrt() + 1e-5;
+                let w = self.curl[id];
+                self.u[id] += configs.vorticity * (ny_ / len) * w * dt;
+                self.v[id] -= configs.vorticity * (nx_ / len) * w * dt;
+            }
+        }
+    }
+
+    fn velocity_step(&mut self, configs: &FluidConfigs) {
+        let (nx, ny, iterations) = (self.nx, self.ny, configs.iterations);
+        let dt = configs.time_step;
+        if configs.viscosity > 0.0 {
+            std::mem::swap(&mut self.u, &mut self.u_prev);
+            std::mem::swap(&mut self.v, &mut self.v_prev);
+            diffuse(nx, ny, 1, &mut self.u, &self.u_prev, configs.viscosity, dt, iterations);
+            diffuse(nx, ny, 2, &mut self.v, &self.v_prev, configs.viscosity, dt, iterations);
+            project(nx, ny, &mut self.u, &mut self.v, &mut self.u_prev, &mut self.v_prev, iterations);
+        }
+        std::mem::swap(&mut self.u, &mut self.u_prev);
+        std::mem::swap(&mut self.v, &mut self.v_prev);
+        advect(nx, ny, 1, &mut self.u, &self.u_prev, &self.u_prev, &self.v_prev, dt);
+        advect(nx, ny, 2, &mut self.v, &self.v_prev, &self.u_prev, &self.v_prev, dt);
+        project(nx, ny, &mut self.u, &mut self.v, &mut self.u_prev, &mut self.v_prev, iterations);
+    }
+
+    fn scalar_step(&mut self, configs: &FluidConfigs) {
+        let (nx, ny) = (self.nx, self.ny);
+        let dt = configs.time_step;
+        std::mem::swap(&mut self.temperature, &mut self.temperature_prev);
+        advect(nx, ny, 0, &mut self.temperature, &self.temperature_prev, &self.u, &self.v, dt);
+        std::mem::swap(&mut self.fuel, &mut self.fuel_prev);
+        advect(nx, ny, 0, &mut self.fuel, &self.fuel_prev, &self.u, &self.v, dt);
+    }
+}
+
+/// Boundary kinds: 0 for scalars, 1 for horizontal and 2 for vertical
+/// velocities, 3 for the pressure. The side walls are solid while the top and
+/// bottom are open so that hot gas can leave the domain and fresh air enter.
+fn set_boundary(nx: usize, ny: usize, b: u8, x: &mut [f32]) {
+    let idx = |i: usize, j: usize| i + j * (nx + 2);
+    for j in 1..=ny {
+        x[idx(0, j)] = if b == 1 { -x[idx(1, j)] } else { x[idx(1, j)] };
+        x[idx(nx + 1, j)] = if b == 1 { -x[idx(nx, j)] } else { x[idx(nx, j)] };
+    }
+    for i in 1..=nx {
+        x[idx(i, 0)] = if b == 3 { 0.0 } else { x[idx(i, 1)] };
+        x[idx(i, ny + 1)] = x[idx(i, ny)];
+    }
+    x[idx(0, 0)] = 0.5 * (x[idx(1, 0)] + x[idx(0, 1)]);
+    x[idx(0, ny + 1)] = 0.5 * (x[idx(1, ny + 1)] + x[idx(0, ny)]);
+    x[idx(nx + 1, 0)] = 0.5 * (x[idx(nx, 0)] + x[idx(nx + 1, 1)]);
+    x[idx(nx + 1, ny + 1)] = 0.5 * (x[idx(nx, ny + 1)] + x[idx(nx + 1, ny)]);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn linear_solve(nx: usize, ny: usize, b: u8, x: &mut [f32], x0: &[f32], a: f32, c: f32, iterations: usize) {
+    let stride = nx + 2;
+    for _ in 0..iterations {
+        for j in 1..=ny {
+            for i in 1..=nx {
+                let id = i + j * stride;
+                x[id] = (x0[id] + a * (x[id - 1] + x[id + 1] + x[id - stride] + x[id + stride])) / c;
+            }
+        }
+        set_boundary(nx, ny, b, x);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn diffuse(nx: usize, ny: usize, b: u8, x: &mut [f32], x0: &[f32], diffusion: f32, dt: f32, iterations: usize) {
+    let a = dt * diffusion;
+    linear_solve(nx, ny, b, x, x0, a, 1.0 + 4.0 * a, iterations);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn advect(nx: usize, ny: usize, b: u8, d: &mut [f32], d0: &[f32], u: &[f32], v: &[f32], dt: f32) {
+    let stride = nx + 2;
+    for j in 1..=ny {
+        for i in 1..=nx {
+            let id = i + j * stride;
+            let x = (i as f32 - dt * u[id]).clamp(0.5, nx as f32 + 0.5);
+            let y = (j as f32 - dt * v[id]).clamp(0.5, ny as f32 + 0.5);
+            let (i0, j0) = (x.floor() as usize, y.floor() as usize);
+            let (s1, t1) = (x - i0 as f32, y - j0 as f32);
+            let (s0, t0) = (1.0 - s1, 1.0 - t1);
+            d[id] = s0 * (t0 * d0[i0 + j0 * stride] + t1 * d0[i0 + (j0 + 1) * stride])
+                + s1 * (t0 * d0[i0 + 1 + j0 * stride] + t1 * d0[i0 + 1 + (j0 + 1) * stride]);
+        }
+    }
+    set_boundary(nx, ny, b, d);
+}
+
+/// Removes the divergent part of the velocity field so that it is mass
+/// conserving, using `p` and `div` as scratch buffers.
+fn project(nx: usize, ny: usize, u: &mut [f32], v: &mut [f32], p: &mut [f32], div: &mut [f32], iterations: usize) {
+    let stride = nx + 2;
+    for j in 1..=ny {
+        for i in 1..=nx {
+            let id = i + j * stride;
+            div[id] = -0.5 * (u[id + 1] - u[id - 1] + v[id + stride] - v[id - stride]);
+            p[id] = 0.0;
+        }
+    }
+    set_boundary(nx, ny, 0, div);
+    set_boundary(nx, ny, 3, p);
+    linear_solve(nx, ny, 3, p, div, 1.0, 4.0, iterations);
+    for j in 1..=ny {
+        for i in 1..=nx {
+            let id = i + j * stride;
+            u[id] -= 0.5 * (p[id + 1] - p[id - 1]);
+            v[id] -= 0.5 * (p[id + stride] - p[id - stride]);
+        }
+    }
+    set_boundary(nx, ny, 1, u);
+    set_boundary(nx, ny, 2, v);
+}
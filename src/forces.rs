@@ -0,0 +1,17 @@
+use noise::NoiseFn;
+use crate::configs::ForceConfigs;
+
+/// Fills `wind` with the horizontal displacement per frame of every row.
+/// The global wind is disturbed by gusts that vary with height and time, so
+/// the flames bend instead of just leaning over.
+pub fn update_wind(
+    wind: &mut [f64],
+    configs: &ForceConfigs,
+    noise_function: &impl NoiseFn<f64, 2>,
+    t: f64,
+) {
+    for (y, val) in wind.iter_mut().enumerate() {
+        let gust = noise_function.get([y as f64 * configs.gust_length_scale, t]);
+        *val = configs.wind + configs.gust_strength * gust;
+    }
+}
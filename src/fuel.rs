@@ -0,0 +1,80 @@
+use crate::configs::FuelConfigs;
+use crate::rng::Rng;
+
+/// Fills the bottom `bed_height` rows with a randomly varying amount of fuel
+/// and lights it at `ignition_position`. Any existing heat is removed so that
+/// the fire only starts from there.
+pub fn initialise_fuel(
+    fuel: &mut [u8],
+    buf: &mut [u8],
+    w: usize,
+    h: usize,
+    configs: &FuelConfigs,
+    obstacles: &[bool],
+    rng: &mut Rng,
+) {
+    fuel.fill(0);
+    buf.fill(0);
+    let bed_height = configs.bed_height.min(h);
+    for y in (h - bed_height)..h {
+        for x in 0..w {
+            let i = x + y * w;
+            if !obstacles[i] {
+                fuel[i] = rng.gen_range(configs.amount / 2, configs.amount);
+            }
+        }
+    }
+
+    let x = ((configs.ignition_position.clamp(0.0, 1.0) * w as f64) as usize).min(w - 1);
+    for y in (h - bed_height)..h {
+        let i = x + y * w;
+        if fuel[i] > 0 {
+            buf[i] = buf[i].max(configs.flame_temperature);
+        }
+    }
+}
+
+/// Burns the fuel. A cell with fuel catches fire when it or one of its
+/// neighbours was at least at the ignition temperature in `original`; a
+/// burning cell is kept at the flame temperature in `new` and consumes its
+/// fuel until it goes out.
+pub fn burn_fuel(
+    original: &[u8],
+    new: &mut [u8],
+    fuel: &mut [u8],
+    w: usize,
+    h: usize,
+    configs: &FuelConfigs,
+    rng: &mut Rng,
+) {
+    for y in 0..h {
+        for x in 0..w {
+            let i = x + y * w;
+            if fuel[i] == 0 {
+                continue;
+            }
+            let burning = original[i] >= configs.ignition_temperature;
+            let ignited = burning || {
+                let mut hottest = 0;
+                if x > 0 {
+                    hottest = hottest.max(original[i - 1]);
+                }
+                if x + 1 < w {
+                    hottest = hottest.max(original[i + 1]);
+                }
+                if y > 0 {
+                    hottest = hottest.max(original[i - w]);
+                }
+                if y + 1 < h {
+                    hottest = hottest.max(original[i + w]);
+                }
+                hottest >= configs.ignition_temperature
+                    && rng.gen_f64() < configs.spread_chance
+            };
+            if ignited {
+                fuel[i] = fuel[i].saturating_sub(configs.burn_rate);
+                new[i] = new[i].max(configs.flame_temperature);
+            }
+        }
+    }
+}
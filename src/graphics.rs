@@ -0,0 +1,265 @@
+use std::io::Write;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use crate::color_maps::ColorLut;
+
+/// Number of palette registers the color map is quantized to for Sixel, one
+/// more register holds the obstacle color.
+const SIXEL_COLORS: usize = 64;
+/// Maximum size of a base64 chunk in a Kitty graphics escape.
+const KITTY_CHUNK_SIZE: usize = 4096;
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Appends the standard base64 encoding of `data`, with padding.
+pub fn encode_base64(out: &mut Vec<u8>, data: &[u8]) {
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let bits = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize]);
+            } else {
+                out.push(b'=');
+            }
+        }
+    }
+}
+
+/// Encodes the frame as a Sixel image. The colors of the color map are
+/// quantized to [`SIXEL_COLORS`] palette registers.
+pub fn encode_sixel(
+    out: &mut Vec<u8>,
+    heat: &[u8],
+    obstacles: &[bool],
+    w: usize,
+    h: usize,
+    color_lut: &ColorLut,
+    obstacle_color: [u8; 4],
+) {
+    // each register gets the mean color of the values mapped to it
+    let values_per_color = 256 / SIXEL_COLORS;
+    let mut palette: Vec<[u8; 4]> = (0..SIXEL_COLORS)
+        .map(|register| {
+            let mut sum = [0usize; 3];
+            for value in register * values_per_color..(register + 1) * values_per_color {
+                let color = color_lut.color(value as u8);
+                for (s, &c) in sum.iter_mut().zip(&color) {
+                    *s += usize::from(c);
+                }
+            }
+            let [r, g, b] = sum.map(|s| (s / values_per_color) as u8);
+            [r, g, b, 255]
+        })
+        .collect();
+    palette.push(obstacle_color);
+    let registers: Vec<u8> = heat
+        .iter()
+        .zip(obstacles)
+        .map(|(&val, &solid)| if solid {
+            SIXEL_COLORS as u8
+        } else {
+            val / values_per_color as u8
+        })
+        .collect();
+
+    // DCS with transparent background, then the raster size and the palette
+    let _ = write!(out, "\x1bP0;1;0q\"1;1;{};{}", w, h);
+    for (register, [r, g, b, _]) in palette.iter().enumerate() {
+        let percent = |c: &u8| u32::from(*c) * 100 / 255;
+        let _ = write!(out, "#{};2;{};{};{}", register, percent(r), percent(g), percent(b));
+    }
+
+    // every band of six rows is drawn in one pass per register it uses
+    let mut sixels = vec![0u8; w];
+    for band in (0..h).step_by(6) {
+        let rows = (h - band).min(6);
+        let mut used = vec![false; palette.len()];
+        for &register in &registers[band * w..(band + rows) * w] {
+            used[usize::from(register)] = true;
+        }
+        for register in (0..palette.len()).filter(|&register| used[register]) {
+            for (x, sixel) in sixels.iter_mut().enumerate() {
+                *sixel = (0..rows)
+                    .filter(|&row| usize::from(registers[x + (band + row) * w]) == register)
+                    .fold(0, |bits, row| bits | 1 << row);
+            }
+            let _ = write!(out, "#{}", register);
+            write_sixel_runs(out, &sixels);
+            out.push(b'$');
+        }
+        out.push(b'-');
+    }
+    out.extend_from_slice(b"\x1b\\");
+}
+
+/// Writes a row of sixels with run-length encoding, leaving out the empty
+/// sixels at its end.
+fn write_sixel_runs(out: &mut Vec<u8>, sixels: &[u8]) {
+    let end = sixels.iter().rposition(|&bits| bits != 0).map_or(0, |i| i + 1);
+    let mut x = 0;
+    while x < end {
+        let run = sixels[x..end].iter().take_while(|&&bits| bits == sixels[x]).count();
+        let char = 63 + sixels[x];
+        if run > 3 {
+            let _ = write!(out, "!{}", run);
+            out.push(char);
+        } else {
+            out.extend(std::iter::repeat_n(char, run));
+        }
+        x += run;
+    }
+}
+
+/// Encodes the frame with the Kitty graphics protocol as zlib compressed
+/// RGB. Every frame replaces the image with id 1 in place, without moving
+/// the cursor.
+pub fn encode_kitty(
+    out: &mut Vec<u8>,
+    heat: &[u8],
+    obstacles: &[bool],
+    w: usize,
+    h: usize,
+    color_lut: &ColorLut,
+    obstacle_color: [u8; 4],
+) {
+    let mut encoder = ZlibEncoder::new(Vec::with_capacity(w * h), Compression::fast());
+    for (&val, &solid) in heat.iter().zip(obstacles) {
+        let color = if solid { obstacle_color } else { color_lut.color(val) };
+        let _ = encoder.write_all(&color[..3]);
+    }
+    let compressed = encoder.finish().unwrap_or_default();
+    let mut data = Vec::with_capacity(compressed.len() * 4 / 3 + 4);
+    encode_base64(&mut data, &compressed);
+
+    let chunks: Vec<&[u8]> = data.chunks(KITTY_CHUNK_SIZE).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        if i == 0 {
+            let _ = write!(out, "\x1b_Ga=T,i=1,p=1,f=24,o=z,s={},v={},q=2,C=1,m={};", w, h, more);
+        } else {
+            let _ = write!(out, "\x1b_Gm={};", more);
+        }
+        out.extend_from_slice(chunk);
+        out.extend_from_slice(b"\x1b\\");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use flate2::read::ZlibDecoder;
+    use crate::color_maps::GrayColorMap;
+    use crate::rng::Rng;
+    use super::*;
+
+    const SIXEL_GOLDEN_PATH: &str = "tests/data/gray-6x7.six";
+
+    fn decode_base64(data: &[u8]) -> Vec<u8> {
+        let bits: Vec<u32> = data
+            .iter()
+            .filter(|&&c| c != b'=')
+            .map(|c| BASE64_ALPHABET.iter().position(|a| a == c).unwrap() as u32)
+            .collect();
+        let mut out = Vec::new();
+        for group in bits.chunks(4) {
+            let word = group.iter().enumerate().fold(0, |word, (i, &b)| word | b << (18 - 6 * i));
+            out.extend(&word.to_be_bytes()[1..group.len()]);
+        }
+        out
+    }
+
+    #[test]
+    fn base64_matches_rfc_4648() {
+        for (input, expected) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foobar", "Zm9vYmFy")] {
+            let mut out = Vec::new();
+            encode_base64(&mut out, input.as_bytes());
+            assert_eq!(out, expected.as_bytes());
+            assert_eq!(decode_base64(&out), input.as_bytes());
+        }
+    }
+
+    /// Six full rows with an obstacle in the top right corner above a cold row,
+    /// so the first band draws two registers and the second one.
+    fn sixel_frame() -> Vec<u8> {
+        let (w, h) = (6, 7);
+        let mut heat = vec![255u8; w * h];
+        heat[6 * w..].fill(0);
+        let mut obstacles = vec![false; w * h];
+        obstacles[5] = true;
+        let mut out = Vec::new();
+        encode_sixel(&mut out, &heat, &obstacles, w, h, &ColorLut::new(&GrayColorMap::new()), [255, 0, 0, 255]);
+        out
+    }
+
+    /// Set `RUSTY_FIRE_UPDATE_GOLDEN=1` to rewrite the golden stream after an
+    /// intended change of the encoder.
+    #[test]
+    fn sixel_matches_golden_stream() {
+        let out = sixel_frame();
+        if std::env::var_os("RUSTY_FIRE_UPDATE_GOLDEN").is_some() {
+            std::fs::write(SIXEL_GOLDEN_PATH, &out).unwrap();
+        }
+        assert!(out == std::fs::read(SIXEL_GOLDEN_PATH).unwrap(), "sixel stream differs from {}", SIXEL_GOLDEN_PATH);
+
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("\x1bP0;1;0q\"1;1;6;7#0;2;0;0;0#1;2;"));
+        // the gray register averages the values 252 to 255, the last holds the obstacles
+        assert!(text.contains("#63;2;99;99;99#64;2;100;0;0#"));
+        assert!(text.ends_with("#63!5~}$#64!5?@$-#0!6@$-\x1b\\"));
+    }
+
+    #[test]
+    fn sixel_runs_are_length_encoded() {
+        let mut out = Vec::new();
+        write_sixel_runs(&mut out, &[1, 1, 1, 2, 2, 2, 2, 63, 0, 0]);
+        assert_eq!(out, b"@@@!4A~");
+    }
+
+    fn kitty_chunks(w: usize, h: usize, heat: &[u8]) -> Vec<String> {
+        let mut out = Vec::new();
+        encode_kitty(&mut out, heat, &vec![false; w * h], w, h, &ColorLut::new(&GrayColorMap::new()), [0; 4]);
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.ends_with("\x1b\\"));
+        text.split_terminator("\x1b\\").map(String::from).collect()
+    }
+
+    #[test]
+    fn kitty_single_chunk_is_final() {
+        let chunks = kitty_chunks(2, 1, &[0, 255]);
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].starts_with("\x1b_Ga=T,i=1,p=1,f=24,o=z,s=2,v=1,q=2,C=1,m=0;"));
+    }
+
+    #[test]
+    fn kitty_splits_the_payload_into_chunks() {
+        // noise barely compresses, so the payload needs several chunks
+        let (w, h) = (64, 64);
+        let mut rng = Rng::new(1);
+        let heat: Vec<u8> = (0..w * h).map(|_| rng.next_u32() as u8).collect();
+        let chunks = kitty_chunks(w, h, &heat);
+        assert!(chunks.len() > 2);
+
+        let mut payload = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let last = i + 1 == chunks.len();
+            let control = match (i, last) {
+                (0, _) => "\x1b_Ga=T,i=1,p=1,f=24,o=z,s=64,v=64,q=2,C=1,m=1;",
+                (_, false) => "\x1b_Gm=1;",
+                (_, true) => "\x1b_Gm=0;",
+            };
+            let data = chunk.strip_prefix(control).unwrap();
+            if last {
+                assert!(data.len() <= KITTY_CHUNK_SIZE);
+            } else {
+                assert_eq!(data.len(), KITTY_CHUNK_SIZE);
+            }
+            payload.extend_from_slice(data.as_bytes());
+        }
+
+        let mut rgb = Vec::new();
+        ZlibDecoder::new(&decode_base64(&payload)[..]).read_to_end(&mut rgb).unwrap();
+        let expected: Vec<u8> = heat.iter().flat_map(|&v| [v, v, v]).collect();
+        assert_eq!(rgb, expected);
+    }
+}
@@ -0,0 +1,144 @@
+use std::time::{Duration, Instant};
+use crate::audio::AudioReactive;
+use crate::color_maps::ColorLut;
+use crate::configs::{FireConfigs, OutputProtocol};
+use crate::control::{Command, Controls};
+use crate::http::LiveStatus;
+use crate::led_output::LedOutput;
+use crate::simulation::FireSimulation;
+use crate::snapshot::save_snapshot;
+use crate::strip::StripSimulation;
+use crate::terminal::{catch_interrupt, interrupted};
+use crate::timing::{FixedTimestep, Playback};
+
+/// What a headless frame has to do.
+struct Frame<'a> {
+    steps: u32,
+    paused: bool,
+    /// Path to save a snapshot to before stepping.
+    snapshot: Option<String>,
+    fire_configs: &'a mut FireConfigs,
+    color_lut: &'a ColorLut,
+    audio: Option<&'a mut AudioReactive>,
+}
+
+impl Frame<'_> {
+    /// Takes the simulation steps, letting the audio modulate the configs
+    /// before each.
+    fn advance(&mut self, mut step: impl FnMut(&FireConfigs)) {
+        for _ in 0..self.steps {
+            if let Some(audio) = self.audio.as_deref_mut() {
+                audio.step(self.fire_configs);
+            }
+            step(self.fire_configs);
+        }
+    }
+}
+
+/// Drives the LED output and the HTTP server in real time without a window
+/// until interrupted, applying the changes and commands of the controls
+/// between frames. `frame` takes the requested simulation steps and sends the
+/// result. Frames run at `output.fps`.
+fn run_outputs(
+    mut fire_configs: FireConfigs,
+    controls: &Controls,
+    mut audio: Option<AudioReactive>,
+    mut frame: impl FnMut(Frame, &mut LedOutput),
+) -> Result<(), String> {
+    if fire_configs.output_configs.protocol == OutputProtocol::None && !controls.serves_http() {
+        return Err(String::from("running headless needs an LED output or --http-port, set output.protocol"));
+    }
+    catch_interrupt();
+    let mut color_lut = ColorLut::named(&fire_configs.color_map_name);
+    let mut timestep = FixedTimestep::new(
+        fire_configs.timing_configs.simulation_rate,
+        fire_configs.timing_configs.max_steps_per_frame,
+    );
+    let mut led_output = LedOutput::new();
+    let mut playback = Playback::new();
+    let mut last_frame = Instant::now();
+    while !interrupted() {
+        if controls.apply(&mut fire_configs) {
+            color_lut = ColorLut::named(&fire_configs.color_map_name);
+        }
+        let mut snapshot = None;
+        for command in controls.commands() {
+            match command {
+                Command::Pause => playback.paused = true,
+                Command::Resume => playback.paused = false,
+                Command::SaveSnapshot(path) => snapshot = Some(path),
+            }
+        }
+        let frame_time = last_frame.elapsed();
+        last_frame = Instant::now();
+        let steps = if playback.paused { 0 } else { timestep.advance(frame_time.as_secs_f64()) };
+        frame(
+            Frame {
+                steps,
+                paused: playback.paused,
+                snapshot,
+                fire_configs: &mut fire_configs,
+                color_lut: &color_lut,
+                audio: audio.as_mut(),
+            },
+            &mut led_output,
+        );
+        let frame_duration = Duration::from_secs_f64(1.0 / fire_configs.output_configs.fps);
+        if let Some(remaining) = frame_duration.checked_sub(last_frame.elapsed()) {
+            std::thread::sleep(remaining);
+        }
+    }
+    Ok(())
+}
+
+/// Runs the fire for the LED matrix only.
+pub fn run_headless(
+    mut simulation: FireSimulation,
+    fire_configs: FireConfigs,
+    controls: &Controls,
+    audio: Option<AudioReactive>,
+) -> Result<(), String> {
+    let mut pixels = vec![[0u8; 4]; simulation.w * simulation.h];
+    run_outputs(fire_configs, controls, audio, |mut frame, led_output| {
+        if let Some(path) = frame.snapshot.take() {
+            match save_snapshot(&path, &simulation, frame.fire_configs) {
+                Ok(()) => println!("saved snapshot to {}", path),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        frame.advance(|fire_configs| simulation.step(fire_configs));
+        let fire_configs = &*frame.fire_configs;
+        let [r, g, b] = fire_configs.obstacle_configs.color;
+        frame.color_lut.apply_masked(&simulation.buf, &simulation.obstacles, [r, g, b, 255], &mut pixels);
+        led_output.send_frame(&fire_configs.output_configs, &pixels, simulation.w, simulation.h);
+        controls.publish(
+            fire_configs,
+            LiveStatus { paused: frame.paused, seed: simulation.seed, frame: simulation.frame },
+        );
+        if controls.wants_frame() {
+            controls.publish_frame(&pixels, simulation.w, simulation.h);
+        }
+    })
+}
+
+/// Runs the one dimensional fire for the LED strip only.
+pub fn run_strip_headless(
+    fire_configs: FireConfigs,
+    controls: &Controls,
+    audio: Option<AudioReactive>,
+) -> Result<(), String> {
+    let mut strip = StripSimulation::new(&fire_configs);
+    run_outputs(fire_configs, controls, audio, |mut frame, led_output| {
+        if frame.snapshot.is_some() {
+            eprintln!("the LED strip has no snapshots");
+        }
+        frame.advance(|fire_configs| strip.step(fire_configs));
+        let fire_configs = &*frame.fire_configs;
+        let colors: Vec<[u8; 4]> = strip.leds(fire_configs).iter().map(|&heat| frame.color_lut.color(heat)).collect();
+        led_output.send_leds(&fire_configs.output_configs, colors.iter().copied());
+        controls.publish(fire_configs, LiveStatus { paused: frame.paused, seed: strip.seed, frame: strip.frame });
+        if controls.wants_frame() {
+            controls.publish_frame(&colors, colors.len(), 1);
+        }
+    })
+}
@@ -0,0 +1,584 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use crate::configs::FireConfigs;
+use crate::control::{apply_change, Command, ConfigChange};
+use crate::defaults::DEFAULT_HTTP_STREAM_FPS;
+use crate::json::{parse_json, write_json_string, JsonValue};
+
+/// Largest request body accepted.
+const MAX_BODY_SIZE: usize = 1 << 20;
+/// Longest request or header line accepted.
+const MAX_LINE_LENGTH: usize = 8192;
+/// Most headers accepted in a request.
+const MAX_HEADER_COUNT: usize = 64;
+/// Separator of the parts of the frame stream.
+const STREAM_BOUNDARY: &str = "rusty-fire-frame";
+const COLOR_MAP_NAMES: [&str; 5] = ["gray", "magma", "inferno", "plasma", "viridis"];
+const INDEX_HTML: &str = "<!DOCTYPE html>
+<html>
+<head><title>Rusty Fire</title></head>
+<body style=\"margin: 0; background: black\">
+<img src=\"/stream\" style=\"width: 100%; image-rendering: pixelated\">
+</body>
+</html>
+";
+
+/// State of the running fire, as the render loop last published it.
+#[derive(Clone)]
+pub struct LiveStatus {
+    pub paused: bool,
+    pub seed: u64,
+    pub frame: u64,
+}
+
+/// A colored frame for the stream.
+struct StreamFrame {
+    id: u64,
+    at: Instant,
+    w: usize,
+    h: usize,
+    pixels: Vec<[u8; 4]>,
+}
+
+#[derive(Default)]
+struct Published {
+    configs: Option<FireConfigs>,
+    status: Option<LiveStatus>,
+    frame: Option<Arc<StreamFrame>>,
+}
+
+struct Shared {
+    published: Mutex<Published>,
+    new_frame: Condvar,
+    /// Connections waiting for frames, the render loop only copies frames
+    /// while there are any.
+    viewers: AtomicUsize,
+    changes: Sender<ConfigChange>,
+    commands: Sender<Command>,
+    snapshot_path: Option<String>,
+    /// Port the server listens on, the only one requests may be addressed to.
+    port: u16,
+}
+
+/// Counts a connection as a viewer while it lives.
+struct Viewer<'a>(&'a AtomicUsize);
+
+impl<'a> Viewer<'a> {
+    fn new(viewers: &'a AtomicUsize) -> Self {
+        viewers.fetch_add(1, Ordering::Relaxed);
+        Self(viewers)
+    }
+}
+
+impl Drop for Viewer<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Local HTTP server to control the fire from a browser and watch it. The
+/// render loop publishes its configs, status and frames, requests to change
+/// them are queued like the other controls.
+pub struct HttpServer {
+    shared: Arc<Shared>,
+}
+
+impl HttpServer {
+    /// Listens on localhost `port`. `snapshot_path` is where snapshots are
+    /// saved to, `None` if the fire cannot take them.
+    pub fn start(
+        port: u16,
+        changes: Sender<ConfigChange>,
+        commands: Sender<Command>,
+        snapshot_path: Option<String>,
+    ) -> Result<Self, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("could not listen for HTTP on port {}: {}", port, e))?;
+        Self::serve(listener, changes, commands, snapshot_path)
+    }
+
+    fn serve(
+        listener: TcpListener,
+        changes: Sender<ConfigChange>,
+        commands: Sender<Command>,
+        snapshot_path: Option<String>,
+    ) -> Result<Self, String> {
+        let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+        let shared = Arc::new(Shared {
+            published: Mutex::new(Published::default()),
+            new_frame: Condvar::new(),
+            viewers: AtomicUsize::new(0),
+            changes,
+            commands,
+            snapshot_path,
+            port,
+        });
+        let server_shared = Arc::clone(&shared);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let shared = Arc::clone(&server_shared);
+                std::thread::spawn(move || handle_connection(stream, &shared));
+            }
+        });
+        Ok(Self { shared })
+    }
+
+    pub fn publish(&self, fire_configs: &FireConfigs, status: LiveStatus) {
+        let mut published = self.shared.published.lock().unwrap_or_else(|e| e.into_inner());
+        published.configs = Some(fire_configs.clone());
+        published.status = Some(status);
+    }
+
+    /// Whether someone watches the stream and the next frame is due.
+    pub fn wants_frame(&self) -> bool {
+        if self.shared.viewers.load(Ordering::Relaxed) == 0 {
+            return false;
+        }
+        let published = self.shared.published.lock().unwrap_or_else(|e| e.into_inner());
+        published
+            .frame
+            .as_ref()
+            .is_none_or(|frame| frame.at.elapsed().as_secs_f64() >= 1.0 / DEFAULT_HTTP_STREAM_FPS)
+    }
+
+    pub fn publish_frame(&self, pixels: &[[u8; 4]], w: usize, h: usize) {
+        let mut published = self.shared.published.lock().unwrap_or_else(|e| e.into_inner());
+        let id = published.frame.as_ref().map_or(1, |frame| frame.id + 1);
+        let frame = StreamFrame { id, at: Instant::now(), w, h, pixels: pixels.to_vec() };
+        published.frame = Some(Arc::new(frame));
+        self.shared.new_frame.notify_all();
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    host: Option<String>,
+    origin: Option<String>,
+    content_type: Option<String>,
+    body: Vec<u8>,
+}
+
+/// Reads a line of at most [`MAX_LINE_LENGTH`] bytes.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> Result<(), String> {
+    line.clear();
+    reader
+        .take(MAX_LINE_LENGTH as u64 + 1)
+        .read_line(line)
+        .map_err(|e| e.to_string())?;
+    if line.len() > MAX_LINE_LENGTH {
+        return Err(String::from("request line or header too long"));
+    }
+    if !line.ends_with('\n') {
+        return Err(String::from("incomplete request"));
+    }
+    Ok(())
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<Request, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    read_line(&mut reader, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(String::from("invalid request line"));
+    };
+    let method = method.to_string();
+    let path = target.split('?').next().unwrap_or(target).to_string();
+
+    let mut content_length = 0;
+    let mut host = None;
+    let mut origin = None;
+    let mut content_type = None;
+    for count in 0.. {
+        read_line(&mut reader, &mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if count == MAX_HEADER_COUNT {
+            return Err(String::from("too many headers"));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => {
+                    content_length = value.parse().map_err(|_| String::from("invalid content length"))?;
+                }
+                "host" => host = Some(value.to_string()),
+                "origin" => origin = Some(value.to_string()),
+                "content-type" => content_type = Some(value.to_string()),
+                _ => {}
+            }
+        }
+    }
+    if content_length > MAX_BODY_SIZE {
+        return Err(String::from("request body too large"));
+    }
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+    Ok(Request { method, path, host, origin, content_type, body })
+}
+
+/// Whether `host` names this server on the loopback interface. Anything else,
+/// including a missing host, may be a DNS rebinding attack.
+fn is_local_host(host: &str, port: u16) -> bool {
+    ["localhost", "127.0.0.1", "[::1]"].iter().any(|name| host.eq_ignore_ascii_case(&format!("{}:{}", name, port)))
+}
+
+/// Refuses requests a web page on another site could make in the browser of
+/// the user: they must be addressed to this server, come from its own page if
+/// from a page at all, and everything but `GET` has to be JSON, which pages of
+/// other origins cannot send without asking first.
+fn check_request(request: &Request, port: u16) -> Result<(), (&'static str, String)> {
+    if !request.host.as_deref().is_some_and(|host| is_local_host(host, port)) {
+        return Err(("403 Forbidden", String::from("the host has to be localhost")));
+    }
+    if let Some(origin) = &request.origin {
+        if !origin.strip_prefix("http://").is_some_and(|host| is_local_host(host, port)) {
+            return Err(("403 Forbidden", format!("requests from {} are not allowed", origin)));
+        }
+    }
+    let is_json = request.content_type.as_deref().is_some_and(|content_type| {
+        content_type.split(';').next().unwrap_or_default().trim().eq_ignore_ascii_case("application/json")
+    });
+    if request.method != "GET" && !is_json {
+        return Err(("415 Unsupported Media Type", String::from("requests other than GET have to be application/json")));
+    }
+    Ok(())
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) {
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len(),
+    );
+    let _ = stream.write_all(header.as_bytes()).and_then(|()| stream.write_all(body));
+}
+
+fn respond_json(stream: &mut TcpStream, status: &str, json: &str) {
+    respond(stream, status, "application/json", json.as_bytes());
+}
+
+fn respond_error(stream: &mut TcpStream, status: &str, error: &str) {
+    let mut json = String::from("{\"error\": ");
+    write_json_string(&mut json, error);
+    json.push('}');
+    respond_json(stream, status, &json);
+}
+
+/// All configs as a flat JSON object of their textual values.
+pub fn configs_json(fire_configs: &FireConfigs) -> String {
+    let mut json = String::from("{");
+    for (i, (key, value)) in fire_configs.entries().iter().enumerate() {
+        json.push_str(if i == 0 { "\n  " } else { ",\n  " });
+        write_json_string(&mut json, key);
+        json.push_str(": ");
+        write_json_string(&mut json, value);
+    }
+    json.push_str("\n}\n");
+    json
+}
+
+/// The changes a flat JSON object of config values asks for. Numbers and
+/// booleans are taken as they are, `null` clears optional values.
+pub fn config_changes(body: &[u8]) -> Result<Vec<ConfigChange>, String> {
+    let text = std::str::from_utf8(body).map_err(|_| String::from("invalid UTF-8"))?;
+    let JsonValue::Object(entries) = parse_json(text)? else {
+        return Err(String::from("expected an object of config values"));
+    };
+    entries
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                JsonValue::String(value) => value,
+                JsonValue::Number(value) => value.to_string(),
+                JsonValue::Bool(value) => value.to_string(),
+                JsonValue::Null => String::from("none"),
+                _ => return Err(format!("invalid value for {}", key)),
+            };
+            Ok(ConfigChange { key, value })
+        })
+        .collect()
+}
+
+fn encode_png(frame: &StreamFrame) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, frame.w as u32, frame.h as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::Fast);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(frame.pixels.as_flattened()).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(data)
+}
+
+/// Id of the last published frame, frames are only published while someone
+/// watches, so it may be stale.
+fn latest_frame_id(shared: &Shared) -> u64 {
+    let published = shared.published.lock().unwrap_or_else(|e| e.into_inner());
+    published.frame.as_ref().map_or(0, |frame| frame.id)
+}
+
+/// Waits for a frame newer than `after`, `None` after a few seconds without.
+fn next_frame(shared: &Shared, after: u64) -> Option<Arc<StreamFrame>> {
+    let published = shared.published.lock().unwrap_or_else(|e| e.into_inner());
+    let (published, _) = shared
+        .new_frame
+        .wait_timeout_while(published, Duration::from_secs(5), |published| {
+            published.frame.as_ref().is_none_or(|frame| frame.id <= after)
+        })
+        .unwrap_or_else(|e| e.into_inner());
+    published.frame.clone().filter(|frame| frame.id > after)
+}
+
+/// Sends the updated configs after validating them on a copy of the
+/// published ones, so invalid requests are refused as a whole.
+fn set_configs(stream: &mut TcpStream, shared: &Shared, changes: Vec<ConfigChange>) {
+    let Some(mut configs) = shared.published.lock().unwrap_or_else(|e| e.into_inner()).configs.clone() else {
+        return respond_error(stream, "503 Service Unavailable", "the fire has not started yet");
+    };
+    for change in &changes {
+        if let Err(e) = apply_change(&mut configs, change) {
+            return respond_error(stream, "400 Bad Request", &e);
+        }
+    }
+    for change in changes {
+        let _ = shared.changes.send(change);
+    }
+    respond_json(stream, "200 OK", &configs_json(&configs));
+}
+
+fn send_command(stream: &mut TcpStream, shared: &Shared, command: Command) {
+    let _ = shared.commands.send(command);
+    respond_json(stream, "202 Accepted", "{}");
+}
+
+fn handle_connection(mut stream: TcpStream, shared: &Shared) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+    let request = match read_request(&mut stream) {
+        Ok(request) => request,
+        Err(e) => return respond_error(&mut stream, "400 Bad Request", &e),
+    };
+    if let Err((status, e)) = check_request(&request, shared.port) {
+        return respond_error(&mut stream, status, &e);
+    }
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => respond(&mut stream, "200 OK", "text/html; charset=utf-8", INDEX_HTML.as_bytes()),
+        ("GET", "/configs") => {
+            let configs = shared.published.lock().unwrap_or_else(|e| e.into_inner()).configs.clone();
+            match configs {
+                Some(configs) => respond_json(&mut stream, "200 OK", &configs_json(&configs)),
+                None => respond_error(&mut stream, "503 Service Unavailable", "the fire has not started yet"),
+            }
+        }
+        ("PUT" | "POST" | "PATCH", "/configs") => match config_changes(&request.body) {
+            Ok(changes) => set_configs(&mut stream, shared, changes),
+            Err(e) => respond_error(&mut stream, "400 Bad Request", &e),
+        },
+        ("GET", "/color_maps") => {
+            let mut json = String::from("[");
+            for (i, name) in COLOR_MAP_NAMES.iter().enumerate() {
+                if i > 0 {
+                    json.push_str(", ");
+                }
+                write_json_string(&mut json, name);
+            }
+            json.push_str("]\n");
+            respond_json(&mut stream, "200 OK", &json);
+        }
+        ("PUT" | "POST", "/color_map") => {
+            let name = match std::str::from_utf8(&request.body).map_err(|e| e.to_string()).and_then(parse_json) {
+                Ok(JsonValue::String(name)) => name.to_ascii_lowercase(),
+                _ => return respond_error(&mut stream, "400 Bad Request", "expected the name of a color map as a JSON string"),
+            };
+            if !COLOR_MAP_NAMES.contains(&name.as_str()) {
+                return respond_error(&mut stream, "400 Bad Request", &format!("unknown color map {}", name));
+            }
+            set_configs(&mut stream, shared, vec![ConfigChange { key: String::from("color_map"), value: name }]);
+        }
+        ("GET", "/status") => {
+            let status = shared.published.lock().unwrap_or_else(|e| e.into_inner()).status.clone();
+            match status {
+                Some(status) => respond_json(
+                    &mut stream,
+                    "200 OK",
+                    &format!(
+                        "{{\"paused\": {}, \"seed\": {}, \"frame\": {}}}\n",
+                        status.paused, status.seed, status.frame,
+                    ),
+                ),
+                None => respond_error(&mut stream, "503 Service Unavailable", "the fire has not started yet"),
+            }
+        }
+        ("POST", "/pause") => send_command(&mut stream, shared, Command::Pause),
+        ("POST", "/resume") => send_command(&mut stream, shared, Command::Resume),
+        ("POST", "/snapshot") => match &shared.snapshot_path {
+            Some(path) => send_command(&mut stream, shared, Command::SaveSnapshot(path.clone())),
+            None => respond_error(&mut stream, "409 Conflict", "snapshots need the two dimensional fire"),
+        },
+        ("GET", "/frame.png") => {
+            let _viewer = Viewer::new(&shared.viewers);
+            match next_frame(shared, latest_frame_id(shared)).map(|frame| encode_png(&frame)) {
+                Some(Ok(png)) => respond(&mut stream, "200 OK", "image/png", &png),
+                Some(Err(e)) => respond_error(&mut stream, "500 Internal Server Error", &e),
+                None => respond_error(&mut stream, "503 Service Unavailable", "no frame rendered"),
+            }
+        }
+        ("GET", "/stream") => {
+            let _viewer = Viewer::new(&shared.viewers);
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+                STREAM_BOUNDARY,
+            );
+            if stream.write_all(header.as_bytes()).is_err() {
+                return;
+            }
+            let mut last = latest_frame_id(shared);
+            while let Some(frame) = next_frame(shared, last) {
+                last = frame.id;
+                let Ok(png) = encode_png(&frame) else {
+                    return;
+                };
+                let part = format!(
+                    "--{}\r\nContent-Type: image/png\r\nContent-Length: {}\r\n\r\n",
+                    STREAM_BOUNDARY,
+                    png.len(),
+                );
+                let sent = stream
+                    .write_all(part.as_bytes())
+                    .and_then(|()| stream.write_all(&png))
+                    .and_then(|()| stream.write_all(b"\r\n"));
+                if sent.is_err() {
+                    return;
+                }
+            }
+        }
+        (_, "/" | "/configs" | "/color_maps" | "/color_map" | "/status" | "/pause" | "/resume" | "/snapshot"
+            | "/frame.png" | "/stream") => {
+            respond_error(&mut stream, "405 Method Not Allowed", "method not allowed")
+        }
+        _ => respond_error(&mut stream, "404 Not Found", "not found"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{channel, Receiver};
+    use super::*;
+
+    struct TestServer {
+        server: HttpServer,
+        port: u16,
+        changes: Receiver<ConfigChange>,
+        commands: Receiver<Command>,
+    }
+
+    /// A server on a free port that has the default configs published.
+    fn start() -> TestServer {
+        let (change_sender, changes) = channel();
+        let (command_sender, commands) = channel();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = HttpServer::serve(listener, change_sender, command_sender, None).unwrap();
+        server.publish(&FireConfigs::default(), LiveStatus { paused: false, seed: 42, frame: 7 });
+        TestServer { server, port, changes, commands }
+    }
+
+    /// Sends the raw `request` and returns the status code and the body.
+    fn send(port: u16, request: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split_once("\r\n\r\n").map_or("", |(_, body)| body);
+        (status, body.to_string())
+    }
+
+    fn json_request(method: &str, path: &str, port: u16, body: &str) -> String {
+        format!(
+            "{} {} HTTP/1.1\r\nHost: localhost:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            port,
+            body.len(),
+            body,
+        )
+    }
+
+    #[test]
+    fn gets_configs_and_status() {
+        let server = start();
+        let (status, body) = send(server.port, &format!("GET /configs HTTP/1.1\r\nHost: 127.0.0.1:{}\r\n\r\n", server.port));
+        assert_eq!(status, 200);
+        assert!(body.contains("\"cooling.strength\": \"0.1\""));
+        let (status, body) = send(server.port, &format!("GET /status HTTP/1.1\r\nHost: localhost:{}\r\n\r\n", server.port));
+        assert_eq!((status, body.as_str()), (200, "{\"paused\": false, \"seed\": 42, \"frame\": 7}\n"));
+        assert!(!server.server.wants_frame());
+    }
+
+    #[test]
+    fn posts_configs_and_commands() {
+        let server = start();
+        let (status, body) = send(server.port, &json_request("POST", "/configs", server.port, "{\"cooling.strength\": 0.3}"));
+        assert_eq!(status, 200);
+        assert!(body.contains("\"cooling.strength\": \"0.3\""));
+        let change = server.changes.try_recv().unwrap();
+        assert_eq!((change.key.as_str(), change.value.as_str()), ("cooling.strength", "0.3"));
+
+        let (status, _) = send(server.port, &json_request("PUT", "/color_map", server.port, "\"magma\""));
+        assert_eq!(status, 200);
+        assert_eq!(server.changes.try_recv().unwrap().value, "magma");
+
+        let (status, _) = send(server.port, &json_request("POST", "/pause", server.port, ""));
+        assert_eq!(status, 202);
+        assert_eq!(server.commands.try_recv().unwrap(), Command::Pause);
+        let (status, _) = send(server.port, &json_request("POST", "/snapshot", server.port, ""));
+        assert_eq!(status, 409);
+    }
+
+    #[test]
+    fn refuses_bad_requests() {
+        let server = start();
+        let port = server.port;
+        let long_header = format!("GET / HTTP/1.1\r\nHost: localhost:{}\r\nX-Long: {}\r\n\r\n", port, "a".repeat(MAX_LINE_LENGTH));
+        let many_headers = format!("GET / HTTP/1.1\r\nHost: localhost:{}\r\n{}\r\n", port, "X-A: b\r\n".repeat(MAX_HEADER_COUNT));
+        let text_plain = json_request("POST", "/configs", port, "{\"output.address\": \"10.0.0.1\"}")
+            .replace("application/json", "text/plain");
+        let foreign_origin = json_request("POST", "/configs", port, "{\"cooling.strength\": 0.3}")
+            .replace("\r\n\r\n", "\r\nOrigin: http://example.com\r\n\r\n");
+        let nested = json_request("PUT", "/configs", port, &"[".repeat(100_000));
+        let cases = [
+            (String::from("nonsense\r\n\r\n"), 400),
+            (long_header, 400),
+            (many_headers, 400),
+            (json_request("PUT", "/configs", port, "{\"cooling.strength\": 0.3, \"fill_percentage\": \"x\"}"), 400),
+            (json_request("PUT", "/configs", port, "[1]"), 400),
+            (nested, 400),
+            (json_request("PUT", "/configs", port, "{\"strip.length\": 1000000000000}"), 400),
+            (json_request("PUT", "/configs", port, "{\"fluid.iterations\": 4000000000}"), 400),
+            (json_request("PUT", "/configs", port, "{\"output.width\": 65536, \"output.height\": 65536}"), 400),
+            (json_request("PUT", "/color_map", port, "magma"), 400),
+            (format!("GET /configs HTTP/1.1\r\nHost: evil.example:{}\r\n\r\n", port), 403),
+            (String::from("GET /configs HTTP/1.1\r\n\r\n"), 403),
+            (foreign_origin, 403),
+            (text_plain, 415),
+            (json_request("DELETE", "/configs", port, ""), 405),
+            (json_request("GET", "/nothing", port, ""), 404),
+        ];
+        for (request, expected) in cases {
+            let (status, body) = send(port, &request);
+            assert_eq!(status, expected, "{:?} got {}", request.lines().next(), body);
+        }
+        assert!(server.changes.try_recv().is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use macroquad::color::{colors, Color};
+use macroquad::shapes::{draw_line, draw_rectangle, draw_rectangle_lines};
+use macroquad::text::draw_text;
+use macroquad::time::get_fps;
+use crate::configs::FireConfigs;
+use crate::simulation::FireSimulation;
+use crate::stats::{Stage, StageTimings};
+use crate::timing::Playback;
+
+/// Number of frames shown in the frame time graph.
+const HISTORY_LENGTH: usize = 240;
+
+/// How much the HUD shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HudMode {
+    Off,
+    /// Frame rate and the most important settings.
+    Basic,
+    /// Additionally per stage timings, a frame time graph and all config values.
+    Full,
+}
+
+impl HudMode {
+    pub fn next(self) -> Self {
+        match self {
+            HudMode::Off => HudMode::Basic,
+            HudMode::Basic => HudMode::Full,
+            HudMode::Full => HudMode::Off,
+        }
+    }
+}
+
+/// On-screen statistics, optionally logged to a CSV file.
+pub struct Hud {
+    pub mode: HudMode,
+    /// Frame times in seconds, oldest first.
+    frame_times: VecDeque<f32>,
+    csv: Option<BufWriter<File>>,
+}
+
+impl Hud {
+    /// Creates the HUD, logging the statistics of every frame to `csv_path` if given.
+    pub fn new(csv_path: Option<&str>) -> Result<Self, String> {
+        let csv = match csv_path {
+            Some(path) => {
+                let error = |e: std::io::Error| format!("could not write stats {}: {}", path, e);
+                let mut file = BufWriter::new(File::create(path).map_err(error)?);
+                let stages: Vec<String> =
+                    Stage::ALL.iter().map(|stage| format!("{}_ms", stage.name())).collect();
+                writeln!(file, "frame,frame_time_ms,steps,{}", stages.join(",")).map_err(error)?;
+                Some(file)
+            }
+            None => None,
+        };
+        Ok(Self {
+            mode: HudMode::Basic,
+            frame_times: VecDeque::with_capacity(HISTORY_LENGTH),
+            csv,
+        })
+    }
+
+    /// Records the statistics of a rendered frame.
+    pub fn record(&mut self, frame: u64, frame_time: f32, steps: u32, timings: &StageTimings) {
+        if self.frame_times.len() == HISTORY_LENGTH {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+
+        if let Some(csv) = &mut self.csv {
+            let stages: Vec<String> = Stage::ALL
+                .iter()
+                .map(|&stage| format!("{:.3}", timings.get(stage).as_secs_f64() * 1000.0))
+                .collect();
+            let row = writeln!(
+                csv,
+                "{},{:.3},{},{}",
+                frame,
+                f64::from(frame_time) * 1000.0,
+                steps,
+                stages.join(","),
+            );
+            if let Err(e) = row {
+                eprintln!("could not write stats: {}", e);
+                self.csv = None;
+            }
+        }
+    }
+
+    pub fn draw(
+        &self,
+        simulation: &FireSimulation,
+        fire_configs: &FireConfigs,
+        playback: &Playback,
+        timings: &StageTimings,
+    ) {
+        if self.mode == HudMode::Off {
+            return;
+        }
+
+        draw_text(format!("FPS: {}", get_fps()).as_str(), 0., 16., 32., colors::WHITE);
+        draw_text(
+            format!(
+                "Mode: {}  Mask: {}  Fuel: {}",
+                fire_configs.simulation_mode.name(),
+                fire_configs.fire_mask_configs.mode.name(),
+                if fire_configs.fuel_configs.enabled { "on" } else { "off" },
+            ).as_str(),
+            0., 40., 24., colors::WHITE,
+        );
+        draw_text(
+            format!(
+                "Wind: {:.1}  Gusts: {:.2}  Buoyancy: {:.1}",
+                fire_configs.force_configs.wind,
+                fire_configs.force_configs.gust_strength,
+                fire_configs.force_configs.buoyancy,
+            ).as_str(),
+            0., 60., 24., colors::WHITE,
+        );
+        draw_text(
+            format!(
+                "Seed: {}  Frame: {}  {}",
+                simulation.seed,
+                simulation.frame,
+                if playback.paused { String::from("Paused") } else { format!("Speed: {}x", playback.speed) },
+            ).as_str(),
+            0., 80., 24., colors::WHITE,
+        );
+
+        if self.mode == HudMode::Full {
+            self.draw_details(simulation, fire_configs, timings);
+        }
+    }
+
+    fn draw_details(&self, simulation: &FireSimulation, fire_configs: &FireConfigs, timings: &StageTimings) {
+        let background = Color::new(0.0, 0.0, 0.0, 0.6);
+        let font_size = 16.;
+
+        // grid, color map and time spent per stage during the last frame
+        let mut lines = vec![
+            format!("Grid: {}x{}  Color map: {}", simulation.w, simulation.h, fire_configs.color_map_name),
+        ];
+        lines.extend(Stage::ALL.iter().map(|&stage| {
+            format!("{:<12}{:>8.3} ms", stage.name(), timings.get(stage).as_secs_f64() * 1000.0)
+        }));
+        lines.push(format!("{:<12}{:>8.3} ms", "total", timings.total().as_secs_f64() * 1000.0));
+        draw_rectangle(0., 86., 240., lines.len() as f32 * 14. + 6., background);
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(line, 4., 100. + i as f32 * 14., font_size, colors::WHITE);
+        }
+
+        // frame time graph with reference lines at 60 and 30 fps
+        let (x0, y0, width, height) = (250., 4., HISTORY_LENGTH as f32, 60.);
+        let ms_to_y = |ms: f32| y0 + height - (ms / 50. * height).min(height);
+        draw_rectangle(x0, y0, width, height, background);
+        for (i, &frame_time) in self.frame_times.iter().enumerate() {
+            let x = x0 + i as f32;
+            draw_line(x, y0 + height, x, ms_to_y(frame_time * 1000.), 1., colors::GREEN);
+        }
+        for (ms, color) in [(1000. / 60., colors::YELLOW), (1000. / 30., colors::RED)] {
+            draw_line(x0, ms_to_y(ms), x0 + width, ms_to_y(ms), 1., color);
+        }
+        draw_rectangle_lines(x0, y0, width, height, 1., colors::WHITE);
+        draw_text("frame time (0-50 ms)", x0 + 4., y0 + 12., 14., colors::WHITE);
+
+        // all config values in columns
+        let entries = fire_configs.entries();
+        let rows = entries.len().div_ceil(2);
+        let (x0, y0, column_width, line_height) = (250., 72., 195., 12.);
+        draw_rectangle(x0, y0, 2. * column_width, rows as f32 * line_height + 6., background);
+        for (i, (key, value)) in entries.iter().enumerate() {
+            let x = x0 + 4. + (i / rows) as f32 * column_width;
+            let y = y0 + 12. + (i % rows) as f32 * line_height;
+            draw_text(&format!("{} = {}", key, value), x, y, 13., colors::WHITE);
+        }
+    }
+}
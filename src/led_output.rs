@@ -0,0 +1,234 @@
+use std::time::Instant;
+use crate::configs::{OutputConfigs, OutputProtocol};
+use crate::network::{DmxSink, OpcSink};
+use crate::pixel_map::{load_pixel_map, PixelMap};
+use crate::serial::SerialSink;
+
+/// Destination of the channel data of a frame, one byte per color channel of
+/// every LED in wiring order.
+pub trait PixelSink {
+    fn send(&mut self, data: &[u8]) -> std::io::Result<()>;
+}
+
+/// Pixels of a row or column of `len` pixels covered by LED `i` of `n`,
+/// at least one.
+fn covered(i: usize, n: usize, len: usize) -> std::ops::Range<usize> {
+    let start = (i * len / n).min(len.saturating_sub(1));
+    start..((i + 1) * len / n).clamp(start + 1, len.max(1))
+}
+
+/// Scales the frame down to the LED matrix by averaging the pixels each LED
+/// covers. The LEDs are returned in wiring order, row by row from the top.
+pub fn scale_to_matrix(
+    pixels: &[[u8; 4]],
+    w: usize,
+    h: usize,
+    matrix_w: usize,
+    matrix_h: usize,
+    serpentine: bool,
+) -> Vec<[u8; 3]> {
+    let mut leds = Vec::with_capacity(matrix_w * matrix_h);
+    for my in 0..matrix_h {
+        let ys = covered(my, matrix_h, h);
+        let row_start = leds.len();
+        for mx in 0..matrix_w {
+            let xs = covered(mx, matrix_w, w);
+            let mut sum = [0usize; 3];
+            for y in ys.clone() {
+                for pixel in &pixels[xs.start + y * w..xs.end + y * w] {
+                    for (s, &c) in sum.iter_mut().zip(pixel) {
+                        *s += usize::from(c);
+                    }
+                }
+            }
+            let count = xs.len() * ys.len();
+            leds.push(sum.map(|s| (s / count) as u8));
+        }
+        if serpentine && my % 2 == 1 {
+            leds[row_start..].reverse();
+        }
+    }
+    leds
+}
+
+/// Channel data of the LEDs with gamma correction and the brightness limit
+/// applied, after `configs.channel_offset` unused channels.
+pub fn channel_data(leds: &[[u8; 3]], configs: &OutputConfigs) -> Vec<u8> {
+    let levels: Vec<u8> = (0..256)
+        .map(|i| ((i as f64 / 255.0).powf(configs.gamma) * configs.brightness * 255.0).round() as u8)
+        .collect();
+    let mut data = vec![0u8; configs.channel_offset];
+    data.extend(leds.iter().flatten().map(|&c| levels[usize::from(c)]));
+    data
+}
+
+/// The settings a sink was created with, it is created again when they
+/// change.
+#[derive(PartialEq)]
+struct SinkTarget {
+    protocol: OutputProtocol,
+    address: Option<String>,
+    universe: u16,
+    channels_per_universe: usize,
+    baud_rate: u32,
+}
+
+/// Layout loaded from `output.map_path`.
+struct LoadedMap {
+    path: String,
+    /// `None` if it could not be loaded.
+    map: Option<PixelMap>,
+    /// Radius of the preview dots, once known.
+    preview_radius: Option<f64>,
+}
+
+/// The LEDs as the output sees a frame.
+pub struct LedPreview {
+    /// Position in the grid and color of every LED.
+    pub leds: Vec<(f64, f64, [u8; 3])>,
+    pub radius: f64,
+}
+
+/// Sends the colored frames to LED controllers at the frame rate of the
+/// output configs. The connection follows changes of the configs, errors
+/// are reported once instead of every frame.
+pub struct LedOutput {
+    sink: Option<Box<dyn PixelSink>>,
+    target: Option<SinkTarget>,
+    last_sent: Option<Instant>,
+    last_error: Option<String>,
+    map: Option<LoadedMap>,
+}
+
+impl LedOutput {
+    pub fn new() -> Self {
+        Self {
+            sink: None,
+            target: None,
+            last_sent: None,
+            last_error: None,
+            map: None,
+        }
+    }
+
+    /// The layout of `output.map_path`, loaded again when the path changes.
+    fn pixel_map(&mut self, configs: &OutputConfigs) -> Option<&mut LoadedMap> {
+        let path = configs.map_path.as_ref()?;
+        if self.map.as_ref().is_none_or(|loaded| &loaded.path != path) {
+            let map = load_pixel_map(path).map_err(|e| self.report(e)).ok();
+            self.map = Some(LoadedMap { path: path.clone(), map, preview_radius: None });
+        }
+        self.map.as_mut()
+    }
+
+    /// The LEDs of the pixel map or the matrix with the colors the frame
+    /// gives them.
+    pub fn preview(&mut self, configs: &OutputConfigs, pixels: &[[u8; 4]], w: usize, h: usize) -> LedPreview {
+        if configs.map_path.is_some() {
+            let Some(LoadedMap { map: Some(map), preview_radius, .. }) = self.pixel_map(configs) else {
+                return LedPreview { leds: Vec::new(), radius: 0.0 };
+            };
+            return LedPreview {
+                leds: map
+                    .grid_positions(w, h)
+                    .into_iter()
+                    .zip(map.sample(pixels, w, h))
+                    .map(|((x, y), color)| (x, y, color))
+                    .collect(),
+                radius: *preview_radius.get_or_insert_with(|| map.preview_radius(w, h)),
+            };
+        }
+        let (matrix_w, matrix_h) = (configs.width.max(1), configs.height.max(1));
+        let (cell_w, cell_h) = (w as f64 / matrix_w as f64, h as f64 / matrix_h as f64);
+        let leds = scale_to_matrix(pixels, w, h, matrix_w, matrix_h, false)
+            .into_iter()
+            .enumerate()
+            .map(|(i, color)| {
+                let (mx, my) = ((i % matrix_w) as f64, (i / matrix_w) as f64);
+                ((mx + 0.5) * cell_w, (my + 0.5) * cell_h, color)
+            })
+            .collect();
+        LedPreview { leds, radius: 0.4 * cell_w.min(cell_h) }
+    }
+
+    /// Whether an output is configured and the next frame is due.
+    pub fn is_due(&self, configs: &OutputConfigs) -> bool {
+        configs.protocol != OutputProtocol::None
+            && self.last_sent.is_none_or(|at| at.elapsed().as_secs_f64() >= 1.0 / configs.fps)
+    }
+
+    /// Sends the frame sampled at the LEDs of the pixel map, or scaled down
+    /// to the LED matrix without one, if it is due.
+    pub fn send_frame(&mut self, configs: &OutputConfigs, pixels: &[[u8; 4]], w: usize, h: usize) {
+        if !self.is_due(configs) {
+            return;
+        }
+        let leds = if configs.map_path.is_some() {
+            match self.pixel_map(configs) {
+                Some(LoadedMap { map: Some(map), .. }) => map.sample(pixels, w, h),
+                // without its layout the frame would end up on the wrong LEDs
+                _ => return,
+            }
+        } else {
+            scale_to_matrix(pixels, w, h, configs.width, configs.height, configs.serpentine)
+        };
+        self.send(configs, &leds);
+    }
+
+    /// Sends one color per LED of a strip, if it is due.
+    pub fn send_leds(&mut self, configs: &OutputConfigs, colors: impl Iterator<Item = [u8; 4]>) {
+        if self.is_due(configs) {
+            let leds: Vec<[u8; 3]> = colors.map(|[r, g, b, _]| [r, g, b]).collect();
+            self.send(configs, &leds);
+        }
+    }
+
+    fn send(&mut self, configs: &OutputConfigs, leds: &[[u8; 3]]) {
+        self.last_sent = Some(Instant::now());
+        let target = Some(SinkTarget {
+            protocol: configs.protocol,
+            address: configs.address.clone(),
+            universe: configs.universe,
+            channels_per_universe: configs.channels_per_universe,
+            baud_rate: configs.baud_rate,
+        });
+        if target != self.target {
+            self.target = target;
+            let address = configs.address.as_deref();
+            let sink = match configs.protocol {
+                OutputProtocol::None => return,
+                OutputProtocol::Opc => OpcSink::new(address.unwrap_or("127.0.0.1"), configs.universe.min(255) as u8)
+                    .map(|sink| Box::new(sink) as Box<dyn PixelSink>),
+                OutputProtocol::E131 => DmxSink::e131(address, configs.universe, configs.channels_per_universe)
+                    .map(|sink| Box::new(sink) as Box<dyn PixelSink>),
+                OutputProtocol::ArtNet => DmxSink::art_net(address, configs.universe, configs.channels_per_universe)
+                    .map(|sink| Box::new(sink) as Box<dyn PixelSink>),
+                OutputProtocol::Serial | OutputProtocol::Adalight => match address {
+                    Some(path) => SerialSink::new(path, configs.baud_rate, configs.protocol == OutputProtocol::Adalight)
+                        .map(|sink| Box::new(sink) as Box<dyn PixelSink>),
+                    None => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "no serial device set")),
+                },
+            };
+            self.sink = match sink {
+                Ok(sink) => Some(sink),
+                Err(e) => {
+                    self.report(format!("could not set up the {} output: {}", configs.protocol.name(), e));
+                    None
+                }
+            };
+        }
+        if let Some(sink) = &mut self.sink {
+            match sink.send(&channel_data(leds, configs)) {
+                Ok(()) => self.last_error = None,
+                Err(e) => self.report(format!("could not send to the {} output: {}", configs.protocol.name(), e)),
+            }
+        }
+    }
+
+    fn report(&mut self, error: String) {
+        if self.last_error.as_ref() != Some(&error) {
+            eprintln!("{}", error);
+            self.last_error = Some(error);
+        }
+    }
+}
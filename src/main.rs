@@ -4,21 +4,66 @@ mod color_maps;
 mod color_map_listed;
 mod fire_handler;
 mod cooling_maps;
+mod fire_masks;
+mod forces;
+mod fluid;
+mod simulation;
+mod obstacles;
+mod cli;
+mod fuel;
+mod rng;
+mod snapshot;
+mod timing;
+mod debug_views;
+mod stats;
+mod hud;
+mod screenshot;
+mod export;
+mod terminal;
+mod graphics;
+mod strip;
+mod network;
+mod led_output;
+mod headless;
+mod json;
+mod pixel_map;
+mod serial;
+mod control;
+mod osc;
+mod midi;
+mod http;
+mod audio;
 
-use std::vec;
-use noise::{Fbm, MultiFractal, Perlin};
-use macroquad::window::{Conf, next_frame, request_new_screen_size, screen_height, screen_width};
-use macroquad::texture::{Image, Texture2D, draw_texture};
+use std::path::Path;
+use std::time::Instant;
+use macroquad::Window;
+use macroquad::window::{Conf, clear_background, next_frame, screen_height, screen_width};
+use macroquad::shapes::{draw_circle, draw_circle_lines, draw_rectangle};
 use macroquad::text::draw_text;
-use macroquad::color::{colors};
-use macroquad::rand::{srand, rand, gen_range};
-use macroquad::time::{get_fps};
-use crate::color_map_listed::{INFERNO_LUT, MAGMA_LUT, PLASMA_LUT, VIRIDIS_LUT};
-use crate::color_maps::{ValueToColor, GrayColorMap, ListedColorMap};
+use macroquad::texture::{Image, Texture2D, draw_texture};
+use macroquad::color::{colors, Color};
+use macroquad::time::get_frame_time;
+use macroquad::input::{is_key_pressed, is_mouse_button_down, mouse_position, KeyCode, MouseButton};
+use crate::audio::AudioReactive;
+use crate::cli::{parse_args, AppOptions, USAGE};
+use crate::color_maps::ColorLut;
 use crate::configs::FireConfigs;
-use crate::cooling_maps::{initialise_cooling_map, update_cooling_map};
+use crate::control::{Command, Controls};
+use crate::debug_views::DebugViews;
+use crate::export::export_video;
+use crate::headless::{run_headless, run_strip_headless};
+use crate::http::LiveStatus;
+use crate::hud::Hud;
+use crate::led_output::LedOutput;
 use crate::defaults::{DEFAULT_WINDOW_HEIGHT, DEFAULT_WINDOW_WIDTH};
-use crate::fire_handler::{seed_fire, smooth_and_cool};
+use crate::obstacles::{load_obstacle_mask, paint_obstacles};
+use crate::simulation::FireSimulation;
+use crate::stats::Stage;
+use crate::strip::StripSimulation;
+use crate::terminal::{run_terminal, terminal_grid_size};
+use crate::screenshot::save_screenshot;
+use crate::snapshot::{load_snapshot, save_snapshot};
+use crate::timing::{interpolate, FixedTimestep, Playback};
 
 fn conf() -> Conf {
     Conf {
@@ -31,150 +76,475 @@ fn conf() -> Conf {
     }
 }
 
-#[macroquad::main(conf)]
-async fn main() {
-    // let mode = "debug";
-
-    request_new_screen_size(1280f32, 390f32);
-
+fn main() {
     // Load configurations
     let mut fire_configs = FireConfigs::default();
+    let options = match parse_args(&mut fire_configs, std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    if options.headless && (options.export_path.is_some() || options.terminal.is_some()) {
+        eprintln!("--headless cannot be combined with --export or --terminal");
+        std::process::exit(1);
+    }
 
-    // TODO: Properly handle colormaps
-    fire_configs.set_color_map_name(String::from("gray"));
-    let color_map: Box<dyn ValueToColor> = match fire_configs.color_map_name.to_ascii_lowercase().as_str() {
-        "gray" => Box::new(GrayColorMap::new()),
-        "magma" => Box::new(ListedColorMap::new(MAGMA_LUT)),
-        "inferno" => Box::new(ListedColorMap::new(INFERNO_LUT)),
-        "plasma" => Box::new(ListedColorMap::new(PLASMA_LUT)),
-        "viridis" => Box::new(ListedColorMap::new(VIRIDIS_LUT)),
-        _ => Box::new(GrayColorMap::new()),
+    // OSC, MIDI and HTTP inputs, which apply between the frames of the live modes
+    let controls = match Controls::start(&options) {
+        Ok(controls) => controls,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
     };
 
-    // Define convenience variables
-    let w = screen_width() as usize;
-    let h = screen_height() as usize;
+    // audio modulating the configs before every simulation step
+    let mut audio = match AudioReactive::start(&options, &fire_configs) {
+        Ok(audio) => audio,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    // Seed rngs
-    if fire_configs.seed.is_some() {
-        srand(fire_configs.seed.unwrap());
-    }
-    let noise_function: Fbm<Perlin> = Fbm::<Perlin>::new(rand()).set_octaves(1);
-
-    // Initialise buffers
-    let mut fire_mask: Vec<bool> = Vec::with_capacity(2 * w);
-    let mut buf = vec![0u8; w * h];
-    let mut buf_new = vec![0u8; w * h];
-
-    // Prepare fire mask
-    for _x in 0..w {
-        let rand_num = gen_range(0, 99);
-        if rand_num < fire_configs.fill_percentage {
-            fire_mask.push(true);
+    if options.strip {
+        if options.export_path.is_some() || options.terminal.is_some() {
+            eprintln!("the LED strip cannot be exported or shown in the terminal");
+            std::process::exit(1);
+        }
+        if options.headless {
+            if let Err(e) = run_strip_headless(fire_configs, &controls, audio) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
         } else {
-            fire_mask.push(false);
+            Window::from_config(conf(), run_strip(fire_configs, controls, audio));
         }
+        return;
     }
-    for x in 0..w {
-        fire_mask.push(fire_mask[x]);
+
+    // The grid has the size of the window, also when exporting without one,
+    // or fills the terminal
+    let (w, h) = match options.terminal {
+        Some(mode) => terminal_grid_size(mode),
+        None => (DEFAULT_WINDOW_WIDTH as usize, DEFAULT_WINDOW_HEIGHT as usize),
+    };
+    let mut simulation = initialise_simulation(&mut fire_configs, &options, w, h);
+
+    let result = if let Some(path) = &options.export_path {
+        let color_lut = ColorLut::named(&fire_configs.color_map_name);
+        export_video(path, &mut simulation, &mut fire_configs, &color_lut, audio.as_mut(), &options.export)
+            .map(|()| println!("exported video to {}", path))
+    } else if let Some(mode) = options.terminal {
+        run_terminal(simulation, fire_configs, &controls, audio, mode, options.terminal_fps)
+    } else if options.headless {
+        run_headless(simulation, fire_configs, &controls, audio)
+    } else {
+        Window::from_config(conf(), run(simulation, fire_configs, options, controls, audio));
+        Ok(())
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
     }
+}
 
-    let mut cooling_map = initialise_cooling_map(
-        w,
-        h,
-        &noise_function,
-        fire_configs.cooling_map_configs.length_scale,
-        fire_configs.cooling_map_configs.strength,
-    );
-    let mut cooling_map_debug = initialise_cooling_map(
-        w,
-        h,
-        &noise_function,
-        fire_configs.cooling_map_configs.length_scale,
-        1.0,
-    );
+/// Creates the simulation, or restores it from a snapshot, and pre-rolls it.
+/// Exits on errors.
+fn initialise_simulation(
+    fire_configs: &mut FireConfigs,
+    options: &AppOptions,
+    w: usize,
+    h: usize,
+) -> FireSimulation {
+    let mut simulation = match &options.load_snapshot {
+        Some(path) => match load_snapshot(path) {
+            Ok((simulation, configs)) if simulation.w == w && simulation.h == h => {
+                *fire_configs = configs;
+                simulation
+            }
+            Ok(_) => {
+                eprintln!("snapshot {} was taken with a different grid size", path);
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None => {
+            let mut simulation = FireSimulation::new(fire_configs, w, h);
+            if let Some(path) = &fire_configs.obstacle_configs.mask_path {
+                match load_obstacle_mask(path, w, h) {
+                    Ok(obstacles) => simulation.obstacles = obstacles,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            simulation
+        }
+    };
+
+    if let Some(pre_roll) = options.pre_roll {
+        let frames = simulation.pre_roll(fire_configs, pre_roll);
+        println!("pre-rolled {} frames", frames);
+        simulation.timings = Default::default();
+    }
+    simulation
+}
+
+async fn run(
+    mut simulation: FireSimulation,
+    mut fire_configs: FireConfigs,
+    options: AppOptions,
+    controls: Controls,
+    mut audio: Option<AudioReactive>,
+) {
+    let (w, h) = (simulation.w, simulation.h);
+    let mut color_lut = ColorLut::named(&fire_configs.color_map_name);
+
+    let [r, g, b] = fire_configs.obstacle_configs.color;
+    let obstacle_color = [r, g, b, 255];
 
     // Initialise image and texture
     let mut image = Image::gen_image_color(w as u16, h as u16, colors::BLACK);
     let texture = Texture2D::from_image(&image);
 
-    // Initialise image and texture for debuggung
-    let mut image_debug = Image::gen_image_color(w as u16, h as u16, colors::BLACK);
-    let texture_debug = Texture2D::from_image(&image);
-
-    // Start fire
-    seed_fire(&mut buf, w, h, &fire_mask);
+    // Initialise fixed timestep and the buffers to interpolate between steps
+    let mut timestep = FixedTimestep::new(
+        fire_configs.timing_configs.simulation_rate,
+        fire_configs.timing_configs.max_steps_per_frame,
+    );
+    let mut previous = simulation.buf.clone();
+    let mut interpolated = simulation.buf.clone();
+    let mut playback = Playback::new();
+    let mut debug_views = DebugViews::new();
+    let mut led_output = LedOutput::new();
+    let mut led_preview = false;
+    let mut hud = match Hud::new(options.stats_csv.as_deref()) {
+        Ok(hud) => hud,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    // Initialise running variable
-    let mut ystart: f64 = 0.0;
     loop {
-        // std::thread::sleep(std::time::Duration::from_millis(200));
-
-        // Perform smoothing and cooling
-        smooth_and_cool(
-            &buf,
-            &mut buf_new,
-            w,
-            h,
-            1,
-            &cooling_map,
-            fire_configs.base_height,
+        // apply the changes of the OSC, MIDI and HTTP inputs between steps
+        if controls.apply(&mut fire_configs) {
+            color_lut = ColorLut::named(&fire_configs.color_map_name);
+        }
+        for command in controls.commands() {
+            match command {
+                Command::Pause => playback.paused = true,
+                Command::Resume => playback.paused = false,
+                Command::SaveSnapshot(path) => match save_snapshot(&path, &simulation, &fire_configs) {
+                    Ok(()) => println!("saved snapshot to {}", path),
+                    Err(e) => eprintln!("{}", e),
+                },
+            }
+        }
+
+        // pause, single step and change the speed
+        if is_key_pressed(KeyCode::Space) {
+            playback.toggle_pause();
+        }
+        if is_key_pressed(KeyCode::Equal) {
+            playback.faster();
+        }
+        if is_key_pressed(KeyCode::Minus) {
+            playback.slower();
+        }
+
+        // cycle through simulation and fire mask modes
+        if is_key_pressed(KeyCode::F) {
+            let mode = fire_configs.simulation_mode.next();
+            fire_configs.set_simulation_mode(mode);
+        }
+        if is_key_pressed(KeyCode::M) {
+            let mode = fire_configs.fire_mask_configs.mode.next();
+            fire_configs.set_fire_mask_mode(mode);
+        }
+
+        // save and restore snapshots
+        if is_key_pressed(KeyCode::F5) {
+            match save_snapshot(&options.snapshot_path, &simulation, &fire_configs) {
+                Ok(()) => println!("saved snapshot to {}", options.snapshot_path),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        if is_key_pressed(KeyCode::F9) {
+            match load_snapshot(&options.snapshot_path) {
+                Ok((loaded, configs)) if loaded.w == w && loaded.h == h => {
+                    simulation = loaded;
+                    fire_configs = configs;
+                    previous.copy_from_slice(&simulation.buf);
+                    debug_views.sync_cooling_map(&simulation, &fire_configs);
+                    println!("loaded snapshot from {}", options.snapshot_path);
+                }
+                Ok(_) => eprintln!("snapshot {} was taken with a different grid size", options.snapshot_path),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+
+        // cycle the HUD and toggle debug views
+        if is_key_pressed(KeyCode::H) {
+            hud.mode = hud.mode.next();
+        }
+        if is_key_pressed(KeyCode::Key1) {
+            debug_views.toggle_cooling_map(&simulation, &fire_configs);
+        }
+        if is_key_pressed(KeyCode::Key2) {
+            debug_views.heat = !debug_views.heat;
+        }
+        if is_key_pressed(KeyCode::Key3) {
+            debug_views.fire_mask = !debug_views.fire_mask;
+        }
+        if is_key_pressed(KeyCode::Key4) {
+            debug_views.difference = !debug_views.difference;
+        }
+        if is_key_pressed(KeyCode::Key5) {
+            debug_views.histogram = !debug_views.histogram;
+        }
+        if is_key_pressed(KeyCode::O) {
+            led_preview = !led_preview;
+        }
+
+        // toggle the fuel layer and lay out fresh fuel
+        if is_key_pressed(KeyCode::B) {
+            fire_configs.fuel_configs.enabled = !fire_configs.fuel_configs.enabled;
+            if fire_configs.fuel_configs.enabled {
+                simulation.refuel(&fire_configs);
+            }
+        }
+        if is_key_pressed(KeyCode::R) && fire_configs.fuel_configs.enabled {
+            simulation.refuel(&fire_configs);
+        }
+
+        // adjust wind, gusts and buoyancy
+        let force_configs = &mut fire_configs.force_configs;
+        if is_key_pressed(KeyCode::Left) {
+            force_configs.wind -= 0.1;
+        }
+        if is_key_pressed(KeyCode::Right) {
+            force_configs.wind += 0.1;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            force_configs.buoyancy += 0.1;
+        }
+        if is_key_pressed(KeyCode::Down) {
+            force_configs.buoyancy = (force_configs.buoyancy - 0.1).max(0.0);
+        }
+        if is_key_pressed(KeyCode::G) {
+            force_configs.gust_strength = (force_configs.gust_strength + 0.25) % 1.25;
+        }
+
+        // draw obstacles with the left and erase them with the right mouse button
+        let (mouse_x, mouse_y) = mouse_position();
+        for (button, solid) in [(MouseButton::Left, true), (MouseButton::Right, false)] {
+            if is_mouse_button_down(button) {
+                paint_obstacles(
+                    &mut simulation.obstacles,
+                    w,
+                    h,
+                    mouse_x,
+                    mouse_y,
+                    fire_configs.obstacle_configs.brush_radius,
+                    solid,
+                );
+            }
+        }
+
+        // advance the simulation by as many fixed steps as fit in the frame,
+        // or by a single one on request while paused
+        timestep.configure(
+            fire_configs.timing_configs.simulation_rate * playback.speed,
+            fire_configs.timing_configs.max_steps_per_frame * playback.speed.ceil() as u32,
         );
+        let steps = if playback.paused {
+            u32::from(is_key_pressed(KeyCode::Period))
+        } else {
+            timestep.advance(f64::from(get_frame_time()))
+        };
+        for _ in 0..steps {
+            if let Some(audio) = &mut audio {
+                audio.step(&mut fire_configs);
+            }
+            debug_views.before_step(&simulation, &fire_configs);
+            previous.copy_from_slice(&simulation.buf);
+            simulation.step(&fire_configs);
+        }
+        let heat = if fire_configs.timing_configs.interpolate {
+            interpolate(&previous, &simulation.buf, timestep.alpha(), &mut interpolated);
+            &interpolated
+        } else {
+            &simulation.buf
+        };
 
-        seed_fire(&mut buf_new, w, h, &fire_mask);
+        let mut timings = std::mem::take(&mut simulation.timings);
 
-        // convert buf_new to image by mapping values to colors
-        image.update(
-            (
-                buf_new.iter().map(
-                    |&val| color_map.value_to_color(val, None)
-                ).collect::<Vec<_>>()
-            ).as_slice()
+        // convert heat buffer to image by mapping values to colors
+        let start = Instant::now();
+        color_lut.apply_masked(heat, &simulation.obstacles, obstacle_color, image.get_image_data_mut());
+        timings.record(Stage::ColorMap, start);
+        led_output.send_frame(&fire_configs.output_configs, image.get_image_data(), w, h);
+        controls.publish(
+            &fire_configs,
+            LiveStatus { paused: playback.paused, seed: simulation.seed, frame: simulation.frame },
         );
+        if controls.wants_frame() {
+            controls.publish_frame(image.get_image_data(), w, h);
+        }
 
         // update and draw texture
+        let start = Instant::now();
         texture.update(&image);
+        timings.record(Stage::Upload, start);
         draw_texture(&texture, 0.0, 0.0, colors::WHITE);
 
-        // draw fps for debugging
-        draw_text(format!("FPS: {}", get_fps()).as_str(), 0., 16., 32., colors::WHITE);
-
-        // update cooling map buffer
-        update_cooling_map(
-            &mut cooling_map,
-            w,
-            h,
-            &noise_function,
-            fire_configs.cooling_map_configs.length_scale,
-            fire_configs.cooling_map_configs.strength,
-            ystart,
-        );
+        // show the LEDs as the output samples them over the dimmed fire
+        if led_preview {
+            let preview = led_output.preview(&fire_configs.output_configs, image.get_image_data(), w, h);
+            let radius = preview.radius as f32;
+            draw_rectangle(0.0, 0.0, w as f32, h as f32, Color::from_rgba(0, 0, 0, 192));
+            for (x, y, [r, g, b]) in preview.leds {
+                draw_circle(x as f32, y as f32, radius, Color::from_rgba(r, g, b, 255));
+                draw_circle_lines(x as f32, y as f32, radius, 1.0, colors::DARKGRAY);
+            }
+        }
 
-        update_cooling_map(
-            &mut cooling_map_debug,
-            w,
-            h,
-            &noise_function,
-            fire_configs.cooling_map_configs.length_scale,
-            1.0,
-            ystart,
-        );
+        // save the colored frame together with the configs that produced it
+        if is_key_pressed(KeyCode::P) {
+            let path = Path::new(&options.screenshot_dir)
+                .join(format!("rusty-fire-{}-{}.png", simulation.seed, simulation.frame));
+            let path = path.to_string_lossy();
+            match save_screenshot(
+                &path,
+                image.get_image_data(),
+                w,
+                h,
+                &fire_configs,
+                simulation.seed,
+                simulation.frame,
+            ) {
+                Ok(()) => println!("saved screenshot to {}", path),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
 
-        ystart += fire_configs.cooling_map_configs.length_scale;
+        debug_views.draw(&simulation, &previous, &color_lut);
 
-        // update image buffer
-        buf.copy_from_slice(&buf_new);
+        hud.record(simulation.frame, get_frame_time(), steps, &timings);
+        hud.draw(&simulation, &fire_configs, &playback, &timings);
 
-        image_debug.update(
-            (
-                cooling_map_debug.iter().map(
-                    |&val| color_map.value_to_color(val, None)
-                ).collect::<Vec<_>>()
-            ).as_slice()
-        );
-        texture_debug.update(&image_debug);
-        draw_texture(&texture_debug, 640.0, 0.0, colors::WHITE);
+        next_frame().await
+    }
+}
+
+/// Shows the one dimensional fire as a row of LEDs, wrapped into several rows
+/// if the strip is too long for the window.
+async fn run_strip(mut fire_configs: FireConfigs, controls: Controls, mut audio: Option<AudioReactive>) {
+    let mut color_lut = ColorLut::named(&fire_configs.color_map_name);
+    let mut strip = StripSimulation::new(&fire_configs);
+    let mut timestep = FixedTimestep::new(
+        fire_configs.timing_configs.simulation_rate,
+        fire_configs.timing_configs.max_steps_per_frame,
+    );
+    let mut playback = Playback::new();
+    let mut led_output = LedOutput::new();
+
+    loop {
+        // apply the changes of the OSC, MIDI and HTTP inputs between steps
+        if controls.apply(&mut fire_configs) {
+            color_lut = ColorLut::named(&fire_configs.color_map_name);
+        }
+        for command in controls.commands() {
+            match command {
+                Command::Pause => playback.paused = true,
+                Command::Resume => playback.paused = false,
+                Command::SaveSnapshot(_) => eprintln!("the LED strip has no snapshots"),
+            }
+        }
+
+        if is_key_pressed(KeyCode::Space) {
+            playback.toggle_pause();
+        }
+        let configs = &mut fire_configs.strip_configs;
+        if is_key_pressed(KeyCode::L) {
+            configs.layout = configs.layout.next();
+        }
+        if is_key_pressed(KeyCode::V) {
+            configs.reversed = !configs.reversed;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            configs.sparking = (configs.sparking + 0.05).min(1.0);
+        }
+        if is_key_pressed(KeyCode::Down) {
+            configs.sparking = (configs.sparking - 0.05).max(0.0);
+        }
+
+        let steps = if playback.paused {
+            u32::from(is_key_pressed(KeyCode::Period))
+        } else {
+            timestep.advance(f64::from(get_frame_time()))
+        };
+        for _ in 0..steps {
+            if let Some(audio) = &mut audio {
+                audio.step(&mut fire_configs);
+            }
+            strip.step(&fire_configs);
+        }
+
+        clear_background(colors::BLACK);
+        let leds = strip.leds(&fire_configs);
+        led_output.send_leds(&fire_configs.output_configs, leds.iter().map(|&heat| color_lut.color(heat)));
+        controls.publish(&fire_configs, LiveStatus { paused: playback.paused, seed: strip.seed, frame: strip.frame });
+        if controls.wants_frame() {
+            let pixels: Vec<[u8; 4]> = leds.iter().map(|&heat| color_lut.color(heat)).collect();
+            controls.publish_frame(&pixels, pixels.len(), 1);
+        }
+        let (w, h) = (screen_width(), screen_height());
+        let size = (w / leds.len() as f32).clamp(4.0, 40.0).floor();
+        let per_row = (w / size) as usize;
+        let rows = leds.len().div_ceil(per_row);
+        let y0 = (h - rows as f32 * size) / 2.0;
+        for (i, &heat) in leds.iter().enumerate() {
+            let [r, g, b, _] = color_lut.color(heat);
+            let x = (i % per_row) as f32 * size;
+            let y = y0 + (i / per_row) as f32 * size;
+            draw_rectangle(x + 1.0, y + 1.0, size - 2.0, size - 2.0, Color::from_rgba(r, g, b, 255));
+        }
 
+        let configs = &fire_configs.strip_configs;
+        draw_text(
+            format!(
+                "LEDs: {}  Layout: {}{}  Sparking: {:.2}  Cooling: {}",
+                configs.length,
+                configs.layout.name(),
+                if configs.reversed { " (reversed)" } else { "" },
+                configs.sparking,
+                configs.cooling,
+            ).as_str(),
+            0., 20., 24., colors::WHITE,
+        );
+        draw_text(
+            format!(
+                "Seed: {}  Frame: {}{}",
+                strip.seed,
+                strip.frame,
+                if playback.paused { "  Paused" } else { "" },
+            ).as_str(),
+            0., 40., 24., colors::WHITE,
+        );
 
         next_frame().await
     }
@@ -0,0 +1,228 @@
+use std::collections::VecDeque;
+use std::io::Read;
+use std::sync::mpsc::Sender;
+use crate::control::{ConfigChange, ControlTarget};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MidiBinding {
+    /// MIDI channel from 1 to 16.
+    pub channel: u8,
+    pub controller: u8,
+    pub key: String,
+    pub target: ControlTarget,
+}
+
+/// Which controller changes which config value.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MidiMapping {
+    pub bindings: Vec<MidiBinding>,
+}
+
+impl MidiMapping {
+    /// Parses `CHANNEL:CONTROLLER = KEY RANGE` lines, where the range is
+    /// `MIN..MAX` or a comma separated list of choices. Empty lines and lines
+    /// starting with `#` are skipped.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut bindings = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("invalid MIDI mapping line {}", line);
+            let (control, assignment) = line.split_once('=').ok_or_else(invalid)?;
+            let (channel, controller) = control.trim().split_once(':').ok_or_else(invalid)?;
+            let (key, target) = assignment.trim().split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let channel: u8 = channel.trim().parse().map_err(|_| invalid())?;
+            let controller: u8 = controller.trim().parse().map_err(|_| invalid())?;
+            if !(1..=16).contains(&channel) || controller > 127 {
+                return Err(invalid());
+            }
+            bindings.push(MidiBinding {
+                channel,
+                controller,
+                key: key.to_string(),
+                target: ControlTarget::parse(target)?,
+            });
+        }
+        Ok(Self { bindings })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# CHANNEL:CONTROLLER = KEY MIN..MAX or KEY CHOICE,CHOICE,...\n");
+        for binding in &self.bindings {
+            text.push_str(&format!(
+                "{}:{} = {} {}\n",
+                binding.channel,
+                binding.controller,
+                binding.key,
+                binding.target.to_text(),
+            ));
+        }
+        text
+    }
+
+    pub fn binding(&self, channel: u8, controller: u8) -> Option<&MidiBinding> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.channel == channel && binding.controller == controller)
+    }
+
+    /// Binds the controller to the next key of `learn` if it has no binding
+    /// yet. Returns the new binding.
+    pub fn learn(
+        &mut self,
+        channel: u8,
+        controller: u8,
+        learn: &mut VecDeque<(String, ControlTarget)>,
+    ) -> Option<&MidiBinding> {
+        if self.binding(channel, controller).is_some() {
+            return None;
+        }
+        let (key, target) = learn.pop_front()?;
+        self.bindings.push(MidiBinding { channel, controller, key, target });
+        self.bindings.last()
+    }
+}
+
+/// Picks control change messages out of a MIDI byte stream, following
+/// running status and skipping everything else.
+#[derive(Default)]
+pub struct MidiParser {
+    status: Option<u8>,
+    data: Vec<u8>,
+}
+
+impl MidiParser {
+    /// Feeds the next byte. Returns the channel (1 to 16), controller and
+    /// value once a control change is complete.
+    pub fn feed(&mut self, byte: u8) -> Option<(u8, u8, u8)> {
+        match byte {
+            // real time messages may appear anywhere and do not affect the status
+            0xf8..=0xff => None,
+            0x80..=0xef => {
+                self.status = Some(byte);
+                self.data.clear();
+                None
+            }
+            // system messages cancel the running status
+            0xf0..=0xf7 => {
+                self.status = None;
+                None
+            }
+            _ => {
+                let status = self.status?;
+                self.data.push(byte);
+                let length = match status & 0xf0 {
+                    0xc0 | 0xd0 => 1,
+                    _ => 2,
+                };
+                if self.data.len() < length {
+                    return None;
+                }
+                let data = std::mem::take(&mut self.data);
+                (status & 0xf0 == 0xb0).then(|| ((status & 0x0f) + 1, data[0], data[1]))
+            }
+        }
+    }
+}
+
+/// Reads control changes from the raw MIDI device at `device_path` on a
+/// thread of its own and passes the config changes they map to to
+/// `changes`. While `learn` has keys left, a controller without a binding is
+/// bound to the next one and the mapping saved to `mapping_path`.
+pub fn spawn_midi_input(
+    device_path: &str,
+    mapping_path: &str,
+    mut learn: VecDeque<(String, ControlTarget)>,
+    changes: Sender<ConfigChange>,
+) -> Result<(), String> {
+    let mut mapping = match std::fs::read_to_string(mapping_path) {
+        Ok(text) => MidiMapping::parse(&text).map_err(|e| format!("{}: {}", mapping_path, e))?,
+        Err(_) if !learn.is_empty() => MidiMapping::default(),
+        Err(e) => return Err(format!("could not read MIDI mapping {}: {}", mapping_path, e)),
+    };
+    let mut device = std::fs::File::open(device_path)
+        .map_err(|e| format!("could not open MIDI device {}: {}", device_path, e))?;
+    let mapping_path = mapping_path.to_string();
+    if let Some((key, _)) = learn.front() {
+        println!("MIDI learn: move the controller for {}", key);
+    }
+    std::thread::spawn(move || {
+        let mut parser = MidiParser::default();
+        let mut buf = [0u8; 256];
+        loop {
+            let size = match device.read(&mut buf) {
+                Ok(0) => return,
+                Ok(size) => size,
+                Err(e) => {
+                    eprintln!("could not read MIDI: {}", e);
+                    return;
+                }
+            };
+            for &byte in &buf[..size] {
+                let Some((channel, controller, value)) = parser.feed(byte) else {
+                    continue;
+                };
+                if let Some(binding) = mapping.learn(channel, controller, &mut learn) {
+                    println!("MIDI learn: bound {} to channel {} controller {}", binding.key, channel, controller);
+                    if let Err(e) = std::fs::write(&mapping_path, mapping.to_text()) {
+                        eprintln!("could not save MIDI mapping {}: {}", mapping_path, e);
+                    }
+                    if let Some((key, _)) = learn.front() {
+                        println!("MIDI learn: move the controller for {}", key);
+                    }
+                }
+                if let Some(binding) = mapping.binding(channel, controller) {
+                    let change = ConfigChange { key: binding.key.clone(), value: binding.target.value(value) };
+                    if changes.send(change).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(parser: &mut MidiParser, bytes: &[u8]) -> Vec<(u8, u8, u8)> {
+        bytes.iter().filter_map(|&byte| parser.feed(byte)).collect()
+    }
+
+    #[test]
+    fn parses_control_changes() {
+        let mut parser = MidiParser::default();
+        // running status carries over to the following messages
+        assert_eq!(feed(&mut parser, &[0xb0, 7, 100, 8, 50, 9]), [(1, 7, 100), (1, 8, 50)]);
+        assert_eq!(feed(&mut parser, &[60]), [(1, 9, 60)]);
+        // real time bytes inside a control change are ignored
+        assert_eq!(feed(&mut parser, &[0xb1, 0xf8, 10, 0xfe, 20, 0xfa]), [(2, 10, 20)]);
+        // program changes and notes are skipped, including with running status
+        assert_eq!(feed(&mut parser, &[0xc3, 5, 6, 0x90, 60, 127, 61, 0]), []);
+        // SysEx is skipped and cancels the running status
+        assert_eq!(feed(&mut parser, &[0xb2, 1, 2, 0xf0, 0x7e, 0x10, 0x20, 0xf7, 5, 6]), [(3, 1, 2)]);
+        assert_eq!(feed(&mut parser, &[0xbf, 127, 0]), [(16, 127, 0)]);
+    }
+
+    #[test]
+    fn learned_mapping_round_trips() {
+        let mut learn = VecDeque::from([
+            (String::from("cooling.strength"), ControlTarget::Range(0.1, 0.5)),
+            (String::from("color_map"), ControlTarget::parse("magma,inferno,classic").unwrap()),
+        ]);
+        let mut mapping = MidiMapping::default();
+        let first = mapping.learn(1, 20, &mut learn).cloned().unwrap();
+        assert_eq!((first.key.as_str(), first.channel, first.controller), ("cooling.strength", 1, 20));
+        // a bound controller keeps its binding
+        assert!(mapping.learn(1, 20, &mut learn).is_none());
+        assert_eq!(mapping.learn(16, 127, &mut learn).unwrap().key, "color_map");
+        assert!(mapping.learn(2, 3, &mut learn).is_none());
+        assert_eq!(mapping.bindings.len(), 2);
+        assert_eq!(MidiMapping::parse(&mapping.to_text()).unwrap(), mapping);
+        assert!(MidiMapping::parse("17:1 = fill_percentage 0..100").is_err());
+        assert!(MidiMapping::parse("1:128 = fill_percentage 0..100").is_err());
+    }
+}
@@ -0,0 +1,312 @@
+use std::io::Write;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+use crate::led_output::PixelSink;
+
+pub const OPC_PORT: u16 = 7890;
+pub const E131_PORT: u16 = 5568;
+pub const ARTNET_PORT: u16 = 6454;
+
+/// Source name E1.31 receivers show for this sender.
+const E131_SOURCE_NAME: &[u8] = b"rusty-fire";
+/// Identifies this sender to E1.31 receivers, fixed so they see the same
+/// source across restarts.
+const E131_CID: [u8; 16] = *b"rusty-fire-e131\0";
+/// How long to wait before connecting again after an OPC connection failed.
+const OPC_RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Open Pixel Control "set pixel colors" message for `channel`.
+pub fn opc_message(channel: u8, data: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(4 + data.len());
+    message.push(channel);
+    message.push(0);
+    message.extend_from_slice(&(data.len().min(usize::from(u16::MAX)) as u16).to_be_bytes());
+    message.extend_from_slice(&data[..data.len().min(usize::from(u16::MAX))]);
+    message
+}
+
+/// E1.31 (sACN) data packet carrying up to 512 DMX channels of `universe`.
+pub fn e131_packet(universe: u16, sequence: u8, data: &[u8]) -> Vec<u8> {
+    let data = &data[..data.len().min(512)];
+    let length = 126 + data.len();
+    let flags_and_length = |from: usize| (0x7000 | (length - from) as u16).to_be_bytes();
+    let mut packet = Vec::with_capacity(length);
+
+    // root layer
+    packet.extend_from_slice(&0x0010u16.to_be_bytes());
+    packet.extend_from_slice(&0x0000u16.to_be_bytes());
+    packet.extend_from_slice(b"ASC-E1.17\0\0\0");
+    packet.extend_from_slice(&flags_and_length(16));
+    packet.extend_from_slice(&0x0000_0004u32.to_be_bytes());
+    packet.extend_from_slice(&E131_CID);
+
+    // framing layer
+    packet.extend_from_slice(&flags_and_length(38));
+    packet.extend_from_slice(&0x0000_0002u32.to_be_bytes());
+    let mut source_name = [0u8; 64];
+    source_name[..E131_SOURCE_NAME.len()].copy_from_slice(E131_SOURCE_NAME);
+    packet.extend_from_slice(&source_name);
+    packet.push(100); // priority
+    packet.extend_from_slice(&0u16.to_be_bytes()); // synchronization address
+    packet.push(sequence);
+    packet.push(0); // options
+    packet.extend_from_slice(&universe.to_be_bytes());
+
+    // DMP layer
+    packet.extend_from_slice(&flags_and_length(115));
+    packet.push(0x02);
+    packet.push(0xa1);
+    packet.extend_from_slice(&0u16.to_be_bytes()); // first property address
+    packet.extend_from_slice(&1u16.to_be_bytes()); // address increment
+    packet.extend_from_slice(&(data.len() as u16 + 1).to_be_bytes());
+    packet.push(0); // DMX start code
+    packet.extend_from_slice(data);
+    packet
+}
+
+/// Art-Net ArtDmx packet carrying up to 512 DMX channels of the 15 bit
+/// port address `universe`.
+pub fn artnet_packet(universe: u16, sequence: u8, data: &[u8]) -> Vec<u8> {
+    let data = &data[..data.len().min(512)];
+    // the length has to be even and at least two
+    let length = (data.len() + data.len() % 2).max(2);
+    let mut packet = Vec::with_capacity(18 + length);
+    packet.extend_from_slice(b"Art-Net\0");
+    packet.extend_from_slice(&0x5000u16.to_le_bytes()); // OpDmx
+    packet.extend_from_slice(&14u16.to_be_bytes()); // protocol version
+    packet.push(sequence);
+    packet.push(0); // physical port
+    packet.push((universe & 0xff) as u8); // sub-net and universe
+    packet.push((universe >> 8 & 0x7f) as u8); // net
+    packet.extend_from_slice(&(length as u16).to_be_bytes());
+    packet.extend_from_slice(data);
+    packet.resize(18 + length, 0);
+    packet
+}
+
+/// Resolves `address`, using `default_port` if it has none.
+fn resolve(address: &str, default_port: u16) -> std::io::Result<SocketAddr> {
+    let with_port = if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{}:{}", address, default_port)
+    };
+    with_port.to_socket_addrs()?.next().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, format!("could not resolve {}", address))
+    })
+}
+
+/// Streams frames to an Open Pixel Control server over TCP, reconnecting
+/// when the connection is lost.
+pub struct OpcSink {
+    address: SocketAddr,
+    channel: u8,
+    stream: Option<TcpStream>,
+    last_attempt: Option<Instant>,
+    /// Why the last connection attempt failed.
+    connect_error: String,
+}
+
+impl OpcSink {
+    pub fn new(address: &str, channel: u8) -> std::io::Result<Self> {
+        Ok(Self {
+            address: resolve(address, OPC_PORT)?,
+            channel,
+            stream: None,
+            last_attempt: None,
+            connect_error: String::new(),
+        })
+    }
+}
+
+impl PixelSink for OpcSink {
+    fn send(&mut self, data: &[u8]) -> std::io::Result<()> {
+        if self.stream.is_none() && self.last_attempt.is_none_or(|at| at.elapsed() >= OPC_RECONNECT_INTERVAL) {
+            self.last_attempt = Some(Instant::now());
+            match TcpStream::connect_timeout(&self.address, Duration::from_millis(200)) {
+                Ok(stream) => {
+                    stream.set_nodelay(true)?;
+                    self.stream = Some(stream);
+                }
+                Err(e) => self.connect_error = e.to_string(),
+            }
+        }
+        let Some(stream) = &mut self.stream else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                format!("could not connect to {}: {}", self.address, self.connect_error),
+            ));
+        };
+        let result = stream.write_all(&opc_message(self.channel, data));
+        if result.is_err() {
+            self.stream = None;
+        }
+        result
+    }
+}
+
+/// DMX over UDP, either as E1.31 or as Art-Net. The data is split into
+/// consecutive universes of `channels_per_universe` channels.
+pub struct DmxSink {
+    socket: UdpSocket,
+    /// Destination, `None` to multicast every universe to its E1.31 group.
+    address: Option<SocketAddr>,
+    art_net: bool,
+    first_universe: u16,
+    channels_per_universe: usize,
+    sequence: u8,
+}
+
+impl DmxSink {
+    /// E1.31 sender. Without an address the universes are multicast.
+    pub fn e131(address: Option<&str>, first_universe: u16, channels_per_universe: usize) -> std::io::Result<Self> {
+        Self::new(address.map(|a| resolve(a, E131_PORT)).transpose()?, false, first_universe, channels_per_universe)
+    }
+
+    /// Art-Net sender. Without an address the packets are broadcast.
+    pub fn art_net(address: Option<&str>, first_universe: u16, channels_per_universe: usize) -> std::io::Result<Self> {
+        let address = resolve(address.unwrap_or("255.255.255.255"), ARTNET_PORT)?;
+        Self::new(Some(address), true, first_universe, channels_per_universe)
+    }
+
+    fn new(
+        address: Option<SocketAddr>,
+        art_net: bool,
+        first_universe: u16,
+        channels_per_universe: usize,
+    ) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.set_broadcast(true)?;
+        Ok(Self {
+            socket,
+            address,
+            art_net,
+            first_universe,
+            channels_per_universe: channels_per_universe.clamp(1, 512),
+            sequence: 0,
+        })
+    }
+}
+
+impl PixelSink for DmxSink {
+    fn send(&mut self, data: &[u8]) -> std::io::Result<()> {
+        // Art-Net reserves sequence number 0 for senders that disable
+        // sequencing, E1.31 wraps through it
+        self.sequence = self.sequence.wrapping_add(1).max(u8::from(self.art_net));
+        for (i, chunk) in data.chunks(self.channels_per_universe).enumerate() {
+            let universe = self.first_universe.wrapping_add(i as u16);
+            let packet = if self.art_net {
+                artnet_packet(universe, self.sequence, chunk)
+            } else {
+                e131_packet(universe, self.sequence, chunk)
+            };
+            let address = self.address.unwrap_or_else(|| {
+                let [hi, lo] = universe.to_be_bytes();
+                SocketAddr::from(([239, 255, hi, lo], E131_PORT))
+            });
+            self.socket.send_to(&packet, address)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::TcpListener;
+    use super::*;
+
+    /// Offset of the sequence number in an E1.31 packet.
+    const E131_SEQUENCE: usize = 111;
+    /// Offset of the DMX data in an E1.31 packet, after the start code.
+    const E131_DATA: usize = 126;
+    const ARTNET_SEQUENCE: usize = 12;
+    const ARTNET_DATA: usize = 18;
+
+    fn listener() -> (UdpSocket, String) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let address = socket.local_addr().unwrap().to_string();
+        (socket, address)
+    }
+
+    fn receive(socket: &UdpSocket) -> Vec<u8> {
+        let mut packet = vec![0u8; 1024];
+        let len = socket.recv(&mut packet).unwrap();
+        packet.truncate(len);
+        packet
+    }
+
+    fn frame(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    #[test]
+    fn opc_frame_reaches_the_server() {
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut sink = OpcSink::new(&server.local_addr().unwrap().to_string(), 3).unwrap();
+        let data = frame(300);
+        sink.send(&data).unwrap();
+        let (mut stream, _) = server.accept().unwrap();
+        let mut message = vec![0u8; 304];
+        stream.read_exact(&mut message).unwrap();
+        assert_eq!(message[..4], [3, 0, 1, 44]);
+        assert_eq!(message[4..], data[..]);
+    }
+
+    #[test]
+    fn e131_splits_the_frame_into_universes() {
+        let (socket, address) = listener();
+        let mut sink = DmxSink::e131(Some(&address), 7, 510).unwrap();
+        let data = frame(600);
+        sink.send(&data).unwrap();
+        for (universe, chunk) in [(7u16, &data[..510]), (8, &data[510..])] {
+            let packet = receive(&socket);
+            assert_eq!(packet[..16], *b"\x00\x10\x00\x00ASC-E1.17\0\0\0");
+            assert_eq!(packet.len(), E131_DATA + chunk.len());
+            assert_eq!(packet[E131_SEQUENCE], 1);
+            assert_eq!(packet[E131_SEQUENCE + 2..E131_SEQUENCE + 4], universe.to_be_bytes());
+            // property value count including the start code, then the start code
+            assert_eq!(packet[123..125], (chunk.len() as u16 + 1).to_be_bytes());
+            assert_eq!(packet[125], 0);
+            assert_eq!(packet[E131_DATA..], *chunk);
+        }
+    }
+
+    #[test]
+    fn art_net_splits_the_frame_into_universes() {
+        let (socket, address) = listener();
+        let mut sink = DmxSink::art_net(Some(&address), 0x0123, 510).unwrap();
+        let data = frame(601);
+        sink.send(&data).unwrap();
+        for (universe, chunk) in [(0x0123u16, &data[..510]), (0x0124, &data[510..])] {
+            let packet = receive(&socket);
+            assert_eq!(packet[..12], *b"Art-Net\0\x00\x50\x00\x0e");
+            assert_eq!(packet[ARTNET_SEQUENCE], 1);
+            assert_eq!(packet[14..16], [(universe & 0xff) as u8, (universe >> 8) as u8]);
+            // odd lengths are padded to an even one
+            let length = chunk.len() + chunk.len() % 2;
+            assert_eq!(packet[16..18], (length as u16).to_be_bytes());
+            assert_eq!(packet.len(), ARTNET_DATA + length);
+            assert_eq!(packet[ARTNET_DATA..ARTNET_DATA + chunk.len()], *chunk);
+        }
+    }
+
+    #[test]
+    fn sequence_numbers_wrap() {
+        let (e131_socket, e131_address) = listener();
+        let (artnet_socket, artnet_address) = listener();
+        let mut e131 = DmxSink::e131(Some(&e131_address), 1, 510).unwrap();
+        let mut art_net = DmxSink::art_net(Some(&artnet_address), 0, 510).unwrap();
+        let mut e131_sequence = Vec::new();
+        let mut artnet_sequence = Vec::new();
+        for _ in 0..257 {
+            e131.send(&[1, 2, 3]).unwrap();
+            art_net.send(&[1, 2, 3]).unwrap();
+            e131_sequence.push(receive(&e131_socket)[E131_SEQUENCE]);
+            artnet_sequence.push(receive(&artnet_socket)[ARTNET_SEQUENCE]);
+        }
+        assert_eq!(e131_sequence[253..], [254, 255, 0, 1]);
+        assert_eq!(artnet_sequence[253..], [254, 255, 1, 2]);
+    }
+}
@@ -0,0 +1,48 @@
+use macroquad::texture::Image;
+
+/// Loads an obstacle mask from an image file. The image is stretched over the
+/// `w` x `h` grid and every sufficiently bright, opaque pixel becomes solid.
+pub fn load_obstacle_mask(path: &str, w: usize, h: usize) -> Result<Vec<bool>, String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("could not read obstacle mask {}: {}", path, e))?;
+    let image = Image::from_file_with_format(&bytes, None)
+        .map_err(|e| format!("could not decode obstacle mask {}: {}", path, e))?;
+    let (iw, ih) = (image.width(), image.height());
+    let data = image.get_image_data();
+
+    let mut obstacles = Vec::with_capacity(w * h);
+    for y in 0..h {
+        let iy = y * ih / h;
+        for x in 0..w {
+            let ix = x * iw / w;
+            let [r, g, b, a] = data[ix + iy * iw];
+            let luminance = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+            obstacles.push(a > 127 && luminance > 127);
+        }
+    }
+    Ok(obstacles)
+}
+
+/// Sets all cells within `radius` of (`cx`, `cy`) to `solid`.
+pub fn paint_obstacles(
+    obstacles: &mut [bool],
+    w: usize,
+    h: usize,
+    cx: f32,
+    cy: f32,
+    radius: f32,
+    solid: bool,
+) {
+    let x_min = (cx - radius).floor().max(0.0) as usize;
+    let y_min = (cy - radius).floor().max(0.0) as usize;
+    let x_max = ((cx + radius).ceil().max(0.0) as usize).min(w.saturating_sub(1));
+    let y_max = ((cy + radius).ceil().max(0.0) as usize).min(h.saturating_sub(1));
+    for y in y_min..=y_max {
+        for x in x_min..=x_max {
+            let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+            if dx * dx + dy * dy <= radius * radius {
+                obstacles[x + y * w] = solid;
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::net::{IpAddr, UdpSocket};
+use std::sync::mpsc::Sender;
+use crate::control::ConfigChange;
+
+/// Prefix of the addresses of the config values. The rest of the address is
+/// the config key with `/` in place of `.`, e.g. `/fire/cooling/strength`.
+pub const OSC_PREFIX: &str = "/fire/";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OscArgument {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    Bool(bool),
+}
+
+impl OscArgument {
+    /// Textual form as accepted by [`crate::configs::FireConfigs::set`].
+    pub fn to_config_value(&self) -> String {
+        match self {
+            OscArgument::Int(value) => value.to_string(),
+            OscArgument::Long(value) => value.to_string(),
+            OscArgument::Float(value) => value.to_string(),
+            OscArgument::Double(value) => value.to_string(),
+            OscArgument::String(value) => value.clone(),
+            OscArgument::Bool(value) => value.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub arguments: Vec<OscArgument>,
+}
+
+/// Reads a null terminated string padded to four bytes.
+fn read_string(data: &[u8], pos: &mut usize) -> Result<String, String> {
+    let rest = data.get(*pos..).ok_or("truncated string")?;
+    let end = rest.iter().position(|&byte| byte == 0).ok_or("unterminated string")?;
+    let string = String::from_utf8(rest[..end].to_vec()).map_err(|_| "invalid UTF-8")?;
+    *pos += (end + 4) & !3;
+    Ok(string)
+}
+
+fn read_bytes<const N: usize>(data: &[u8], pos: &mut usize) -> Result<[u8; N], String> {
+    let bytes = data
+        .get(*pos..*pos + N)
+        .ok_or("truncated argument")?
+        .try_into()
+        .map_err(|_| "truncated argument")?;
+    *pos += N;
+    Ok(bytes)
+}
+
+/// Parses an OSC packet, either a message or a bundle, whose messages are
+/// returned in order. Time tags are ignored, bundled messages apply at once.
+pub fn parse_osc_packet(data: &[u8]) -> Result<Vec<OscMessage>, String> {
+    if data.starts_with(b"#bundle\0") {
+        let mut messages = Vec::new();
+        // skip the time tag
+        let mut pos = 16;
+        while pos < data.len() {
+            let size = u32::from_be_bytes(read_bytes(data, &mut pos)?) as usize;
+            let end = pos
+                .checked_add(size)
+                .filter(|&end| end <= data.len())
+                .ok_or("truncated bundle element")?;
+            let element = &data[pos..end];
+            messages.extend(parse_osc_packet(element)?);
+            pos += size;
+        }
+        return Ok(messages);
+    }
+
+    let mut pos = 0;
+    let address = read_string(data, &mut pos)?;
+    if !address.starts_with('/') {
+        return Err(format!("invalid address {}", address));
+    }
+    // messages without a type tag string have no arguments
+    let tags = if pos < data.len() { read_string(data, &mut pos)? } else { String::from(",") };
+    let tags = tags.strip_prefix(',').ok_or("invalid type tags")?;
+    let mut arguments = Vec::new();
+    for tag in tags.chars() {
+        arguments.push(match tag {
+            'i' => OscArgument::Int(i32::from_be_bytes(read_bytes(data, &mut pos)?)),
+            'h' => OscArgument::Long(i64::from_be_bytes(read_bytes(data, &mut pos)?)),
+            'f' => OscArgument::Float(f32::from_be_bytes(read_bytes(data, &mut pos)?)),
+            'd' => OscArgument::Double(f64::from_be_bytes(read_bytes(data, &mut pos)?)),
+            's' | 'S' => OscArgument::String(read_string(data, &mut pos)?),
+            'T' => OscArgument::Bool(true),
+            'F' => OscArgument::Bool(false),
+            _ => return Err(format!("unsupported argument type {}", tag)),
+        });
+    }
+    Ok(vec![OscMessage { address, arguments }])
+}
+
+/// The config change an OSC message asks for.
+pub fn osc_config_change(message: &OscMessage) -> Result<ConfigChange, String> {
+    let key = message
+        .address
+        .strip_prefix(OSC_PREFIX)
+        .ok_or_else(|| format!("unknown OSC address {}", message.address))?
+        .replace('/', ".");
+    let value = message
+        .arguments
+        .first()
+        .ok_or_else(|| format!("OSC message {} has no value", message.address))?
+        .to_config_value();
+    Ok(ConfigChange { key, value })
+}
+
+/// Listens for OSC messages on the UDP `port` of `address` on a thread of its
+/// own and passes the config changes they ask for to `changes`.
+pub fn spawn_osc_server(address: IpAddr, port: u16, changes: Sender<ConfigChange>) -> Result<(), String> {
+    let socket = UdpSocket::bind((address, port))
+        .map_err(|e| format!("could not listen for OSC on {}:{}: {}", address, port, e))?;
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 65536];
+        loop {
+            let size = match socket.recv(&mut buf) {
+                Ok(size) => size,
+                Err(e) => {
+                    eprintln!("could not receive OSC: {}", e);
+                    continue;
+                }
+            };
+            let messages = match parse_osc_packet(&buf[..size]) {
+                Ok(messages) => messages,
+                Err(e) => {
+                    eprintln!("invalid OSC packet: {}", e);
+                    continue;
+                }
+            };
+            for message in &messages {
+                match osc_config_change(message) {
+                    Ok(change) => {
+                        if changes.send(change).is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configs::FireConfigs;
+    use crate::control::apply_change;
+
+    /// `/fire/cooling/strength f 0.25`
+    fn message() -> Vec<u8> {
+        let mut data = b"/fire/cooling/strength\0\0,f\0\0".to_vec();
+        data.extend_from_slice(&0.25f32.to_be_bytes());
+        data
+    }
+
+    fn bundle(size: u32, element: &[u8]) -> Vec<u8> {
+        let mut data = b"#bundle\0".to_vec();
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        data.extend_from_slice(&size.to_be_bytes());
+        data.extend_from_slice(element);
+        data
+    }
+
+    #[test]
+    fn parses_messages_and_bundles() {
+        let expected = OscMessage {
+            address: String::from("/fire/cooling/strength"),
+            arguments: vec![OscArgument::Float(0.25)],
+        };
+        assert_eq!(parse_osc_packet(&message()).unwrap(), vec![expected.clone()]);
+        let element = message();
+        assert_eq!(parse_osc_packet(&bundle(element.len() as u32, &element)).unwrap(), vec![expected.clone()]);
+        let change = osc_config_change(&expected).unwrap();
+        assert_eq!((change.key.as_str(), change.value.as_str()), ("cooling.strength", "0.25"));
+    }
+
+    #[test]
+    fn rejects_malformed_bundle_sizes() {
+        let element = message();
+        // negative as an i32, past the end and overflowing the position
+        for size in [u32::MAX, element.len() as u32 + 1, u32::MAX - 3] {
+            assert!(parse_osc_packet(&bundle(size, &element)).is_err());
+        }
+        assert!(parse_osc_packet(&bundle(4, &[])).is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_config_values() {
+        let mut data = b"/fire/strip/length\0\0,i\0\0".to_vec();
+        data.extend_from_slice(&i32::MAX.to_be_bytes());
+        let messages = parse_osc_packet(&data).unwrap();
+        let change = osc_config_change(&messages[0]).unwrap();
+        let mut fire_configs = FireConfigs::default();
+        assert!(apply_change(&mut fire_configs, &change).is_err());
+        assert_eq!(fire_configs.strip_configs.length, FireConfigs::default().strip_configs.length);
+    }
+}
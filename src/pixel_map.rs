@@ -0,0 +1,174 @@
+use crate::json::{parse_json, JsonValue};
+
+/// Positions of the LEDs of an installation in wiring order, in the units of
+/// the layout file. The y axis points down, as in the grid.
+pub struct PixelMap {
+    pub positions: Vec<(f64, f64)>,
+}
+
+/// Loads a layout from a CSV file with `x,y` lines or a JSON file, chosen by
+/// the extension.
+pub fn load_pixel_map(path: &str) -> Result<PixelMap, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("could not read pixel map {}: {}", path, e))?;
+    let positions = if path.to_ascii_lowercase().ends_with(".json") {
+        parse_json_layout(&text)
+    } else {
+        parse_csv_layout(&text)
+    }
+    .map_err(|e| format!("invalid pixel map {}: {}", path, e))?;
+    if positions.is_empty() {
+        return Err(format!("pixel map {} has no LEDs", path));
+    }
+    Ok(PixelMap { positions })
+}
+
+/// One LED per line. A header line naming the `x` and `y` columns is
+/// optional, without one the first two columns are used. Empty lines and
+/// lines starting with `#` are skipped.
+pub fn parse_csv_layout(text: &str) -> Result<Vec<(f64, f64)>, String> {
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .peekable();
+    let mut columns = (0, 1);
+    if let Some(header) = lines.peek() {
+        let names: Vec<String> = header.split(',').map(|name| name.trim().to_ascii_lowercase()).collect();
+        if names.iter().any(|name| name.parse::<f64>().is_err()) {
+            let column = |axis: &str| {
+                names.iter().position(|name| name == axis)
+                    .ok_or_else(|| format!("header has no {} column", axis))
+            };
+            columns = (column("x")?, column("y")?);
+            lines.next();
+        }
+    }
+    lines
+        .map(|line| {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let field = |i: usize| {
+                fields.get(i)
+                    .and_then(|field| field.parse::<f64>().ok())
+                    .ok_or_else(|| format!("invalid line {}", line))
+            };
+            Ok((field(columns.0)?, field(columns.1)?))
+        })
+        .collect()
+}
+
+/// An array with one entry per LED, each either an `[x, y]` array, an
+/// object with `x` and `y` or an Open Pixel Control layout object with a
+/// `point`. Further coordinates are ignored.
+pub fn parse_json_layout(text: &str) -> Result<Vec<(f64, f64)>, String> {
+    let json = parse_json(text)?;
+    let leds = json.as_array().ok_or("expected an array of LEDs")?;
+    leds.iter()
+        .enumerate()
+        .map(|(i, led)| {
+            let point = match led {
+                JsonValue::Array(_) => Some(led),
+                JsonValue::Object(_) => led.get("point"),
+                _ => None,
+            };
+            let position = match point.and_then(JsonValue::as_array) {
+                Some([x, y, ..]) => x.as_f64().zip(y.as_f64()),
+                Some(_) => None,
+                None => led.get("x").and_then(JsonValue::as_f64).zip(led.get("y").and_then(JsonValue::as_f64)),
+            };
+            position.ok_or_else(|| format!("LED {} has no position", i))
+        })
+        .collect()
+}
+
+impl PixelMap {
+    /// Positions of the LEDs in the grid. The layout is scaled to fit the
+    /// grid, keeping its aspect ratio, and centred.
+    pub fn grid_positions(&self, w: usize, h: usize) -> Vec<(f64, f64)> {
+        let (mut min_x, mut min_y) = (f64::INFINITY, f64::INFINITY);
+        let (mut max_x, mut max_y) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for &(x, y) in &self.positions {
+            (min_x, max_x) = (min_x.min(x), max_x.max(x));
+            (min_y, max_y) = (min_y.min(y), max_y.max(y));
+        }
+        let (grid_w, grid_h) = ((w - 1) as f64, (h - 1) as f64);
+        let scale = [grid_w / (max_x - min_x), grid_h / (max_y - min_y)]
+            .into_iter()
+            .filter(|scale| scale.is_finite())
+            .fold(f64::INFINITY, f64::min);
+        let scale = if scale.is_finite() { scale } else { 0.0 };
+        let offset_x = (grid_w - (max_x - min_x) * scale) / 2.0;
+        let offset_y = (grid_h - (max_y - min_y) * scale) / 2.0;
+        self.positions
+            .iter()
+            .map(|&(x, y)| ((x - min_x) * scale + offset_x, (y - min_y) * scale + offset_y))
+            .collect()
+    }
+
+    /// Colors of the LEDs, bilinearly interpolated between the pixels of the
+    /// frame around them.
+    pub fn sample(&self, pixels: &[[u8; 4]], w: usize, h: usize) -> Vec<[u8; 3]> {
+        self.grid_positions(w, h)
+            .into_iter()
+            .map(|(x, y)| {
+                let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+                let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+                let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+                let pixel = |x: usize, y: usize, c: usize| f64::from(pixels[x + y * w][c]);
+                [0, 1, 2].map(|c| {
+                    let top = pixel(x0, y0, c) * (1.0 - fx) + pixel(x1, y0, c) * fx;
+                    let bottom = pixel(x0, y1, c) * (1.0 - fx) + pixel(x1, y1, c) * fx;
+                    (top * (1.0 - fy) + bottom * fy).round() as u8
+                })
+            })
+            .collect()
+    }
+
+    /// Radius of the dots the preview draws, in grid cells, so neighbouring
+    /// LEDs do not overlap. Uses the median distance to the nearest LED of up
+    /// to 256 LEDs spread over the layout.
+    pub fn preview_radius(&self, w: usize, h: usize) -> f64 {
+        let positions = self.grid_positions(w, h);
+        let stride = positions.len().div_ceil(256);
+        let mut nearest: Vec<f64> = positions
+            .iter()
+            .step_by(stride)
+            .map(|&(x, y)| {
+                positions
+                    .iter()
+                    .map(|&(ox, oy)| (ox - x).hypot(oy - y))
+                    .filter(|&distance| distance > 0.0)
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .filter(|distance| distance.is_finite())
+            .collect();
+        nearest.sort_by(f64::total_cmp);
+        nearest.get(nearest.len() / 2).map_or(8.0, |distance| 0.4 * distance).clamp(1.0, 12.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_and_csv_layouts_match() {
+        let csv = std::fs::read_to_string("layouts/ring-24.csv").unwrap();
+        let positions = parse_csv_layout(&csv).unwrap();
+        assert_eq!(positions.len(), 24);
+        let arrays: Vec<String> = positions.iter().map(|(x, y)| format!("[{}, {}]", x, y)).collect();
+        let objects: Vec<String> = positions.iter().map(|(x, y)| format!("{{\"x\": {}, \"y\": {}}}", x, y)).collect();
+        let points: Vec<String> = positions.iter().map(|(x, y)| format!("{{\"point\": [{}, {}, 0]}}", x, y)).collect();
+        let dir = std::env::temp_dir();
+        for (i, leds) in [arrays, objects, points].iter().enumerate() {
+            let path = dir.join(format!("rusty-fire-{}-ring-{}.json", std::process::id(), i));
+            std::fs::write(&path, format!("[{}]", leds.join(",\n"))).unwrap();
+            let map = load_pixel_map(path.to_str().unwrap());
+            std::fs::remove_file(&path).unwrap();
+            let map = map.unwrap();
+            assert_eq!(map.positions, positions);
+            let expected = load_pixel_map("layouts/ring-24.csv").unwrap();
+            assert_eq!(map.grid_positions(64, 48), expected.grid_positions(64, 48));
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use crate::configs::FireConfigs;
+
+/// Keywords of the PNG text chunks a screenshot carries.
+const CONFIGS_KEYWORD: &str = "rusty-fire configs";
+const FRAME_KEYWORD: &str = "rusty-fire frame";
+
+/// Saves RGBA `pixels` as a PNG whose text chunks hold the configs and the
+/// frame number, so the screenshot can be turned back into its configs with
+/// [`load_screenshot_configs`]. `seed` replaces a random seed in the configs.
+pub fn save_screenshot(
+    path: &str,
+    pixels: &[[u8; 4]],
+    w: usize,
+    h: usize,
+    fire_configs: &FireConfigs,
+    seed: u64,
+    frame: u64,
+) -> Result<(), String> {
+    let error = |e: &dyn std::fmt::Display| format!("could not write screenshot {}: {}", path, e);
+    let file = BufWriter::new(File::create(path).map_err(|e| error(&e))?);
+    let mut encoder = png::Encoder::new(file, w as u32, h as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut configs = fire_configs.clone();
+    configs.seed = Some(seed);
+    encoder
+        .add_itxt_chunk(String::from(CONFIGS_KEYWORD), configs.to_text())
+        .map_err(|e| error(&e))?;
+    encoder
+        .add_itxt_chunk(String::from(FRAME_KEYWORD), frame.to_string())
+        .map_err(|e| error(&e))?;
+
+    let mut writer = encoder.write_header().map_err(|e| error(&e))?;
+    writer.write_image_data(pixels.as_flattened()).map_err(|e| error(&e))?;
+    writer.finish().map_err(|e| error(&e))
+}
+
+/// Reads the configs and the frame number embedded by [`save_screenshot`].
+pub fn load_screenshot_configs(path: &str) -> Result<(FireConfigs, u64), String> {
+    let error = |e: &dyn std::fmt::Display| format!("could not read screenshot {}: {}", path, e);
+    let file = BufReader::new(File::open(path).map_err(|e| error(&e))?);
+    let reader = png::Decoder::new(file).read_info().map_err(|e| error(&e))?;
+
+    let mut configs = None;
+    let mut frame = 0;
+    for chunk in &reader.info().utf8_text {
+        let text = chunk.get_text().map_err(|e| error(&e))?;
+        match chunk.keyword.as_str() {
+            CONFIGS_KEYWORD => {
+                let mut fire_configs = FireConfigs::default();
+                fire_configs.apply_text(&text)?;
+                configs = Some(fire_configs);
+            }
+            FRAME_KEYWORD => {
+                frame = text.parse().map_err(|_| format!("{} has an invalid frame number", path))?;
+            }
+            _ => {}
+        }
+    }
+    let configs = configs.ok_or_else(|| format!("{} has no embedded configs", path))?;
+    Ok((configs, frame))
+}
@@ -0,0 +1,239 @@
+use std::fs::File;
+use std::io::Write;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use crate::led_output::PixelSink;
+
+/// Start of every frame of the framed serial protocol.
+pub const FRAME_MAGIC: &[u8; 2] = b"RF";
+
+/// Frame of the serial protocol: the magic, the payload length as big endian
+/// u16, the payload and the sum of the length and payload bytes modulo 256.
+/// Payloads longer than a u16 can describe are an error.
+pub fn framed_message(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let length = u16::try_from(data.len()).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} bytes do not fit into a serial frame of at most {}", data.len(), u16::MAX),
+        )
+    })?;
+    let length = length.to_be_bytes();
+    let mut message = Vec::with_capacity(data.len() + 5);
+    message.extend_from_slice(FRAME_MAGIC);
+    message.extend_from_slice(&length);
+    message.extend_from_slice(data);
+    let checksum = length.iter().chain(data).fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+    message.push(checksum);
+    Ok(message)
+}
+
+/// Adalight frame: "Ada", the number of LEDs minus one as big endian u16, a
+/// checksum of those two bytes and the RGB data. More LEDs than the header can
+/// count are an error.
+pub fn adalight_message(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let leds = data.len().div_ceil(3).max(1);
+    let [hi, lo] = u16::try_from(leds - 1)
+        .map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} LEDs do not fit into an Adalight frame of at most {}", leds, usize::from(u16::MAX) + 1),
+            )
+        })?
+        .to_be_bytes();
+    let mut message = Vec::with_capacity(6 + leds * 3);
+    message.extend_from_slice(b"Ada");
+    message.extend_from_slice(&[hi, lo, hi ^ lo ^ 0x55]);
+    message.extend_from_slice(data);
+    // pad a partial last LED
+    message.resize(6 + leds * 3, 0);
+    Ok(message)
+}
+
+#[cfg(target_os = "linux")]
+fn baud_constant(baud_rate: u32) -> Option<libc::speed_t> {
+    Some(match baud_rate {
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        460800 => libc::B460800,
+        500000 => libc::B500000,
+        576000 => libc::B576000,
+        921600 => libc::B921600,
+        1000000 => libc::B1000000,
+        1500000 => libc::B1500000,
+        2000000 => libc::B2000000,
+        3000000 => libc::B3000000,
+        4000000 => libc::B4000000,
+        _ => return None,
+    })
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn baud_constant(baud_rate: u32) -> Option<libc::speed_t> {
+    Some(match baud_rate {
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        _ => return None,
+    })
+}
+
+/// Opens a serial port as a raw 8N1 line at `baud_rate`.
+#[cfg(unix)]
+pub fn open_serial_port(path: &str, baud_rate: u32) -> std::io::Result<File> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let speed = baud_constant(baud_rate).ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("unsupported baud rate {}", baud_rate))
+    })?;
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(path)?;
+    let fd = file.as_raw_fd();
+    // SAFETY: the descriptor is open for the lifetime of `file` and the
+    // termios struct is initialised by tcgetattr before it is read.
+    unsafe {
+        let mut termios = std::mem::zeroed::<libc::termios>();
+        if libc::tcgetattr(fd, &mut termios) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        libc::cfmakeraw(&mut termios);
+        termios.c_cflag |= libc::CLOCAL | libc::CREAD;
+        termios.c_cflag &= !(libc::CSTOPB | libc::PARENB | libc::CRTSCTS);
+        if libc::cfsetispeed(&mut termios, speed) != 0
+            || libc::cfsetospeed(&mut termios, speed) != 0
+            || libc::tcsetattr(fd, libc::TCSANOW, &termios) != 0
+        {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+pub fn open_serial_port(_path: &str, _baud_rate: u32) -> std::io::Result<File> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "serial output needs a unix system"))
+}
+
+/// Writes frames to a microcontroller over a serial port, either in the
+/// framed protocol or as Adalight. The port is written on its own thread, so a
+/// stalled device cannot hold up the frames; while it is still busy with the
+/// previous frame new ones are dropped.
+pub struct SerialSink {
+    frames: SyncSender<Vec<u8>>,
+    /// Why the writer thread stopped.
+    errors: Receiver<std::io::Error>,
+    error: Option<String>,
+    adalight: bool,
+}
+
+impl SerialSink {
+    pub fn new(path: &str, baud_rate: u32, adalight: bool) -> std::io::Result<Self> {
+        let mut port = open_serial_port(path, baud_rate)?;
+        let (frames, pending) = sync_channel::<Vec<u8>>(1);
+        let (error_sender, errors) = sync_channel(1);
+        std::thread::spawn(move || {
+            for message in pending {
+                if let Err(e) = port.write_all(&message) {
+                    let _ = error_sender.send(e);
+                    break;
+                }
+            }
+        });
+        Ok(Self { frames, errors, error: None, adalight })
+    }
+}
+
+impl PixelSink for SerialSink {
+    fn send(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let message = if self.adalight { adalight_message(data)? } else { framed_message(data)? };
+        match self.frames.try_send(message) {
+            Ok(()) | Err(TrySendError::Full(_)) => Ok(()),
+            Err(TrySendError::Disconnected(_)) => {
+                let error = self.error.get_or_insert_with(|| {
+                    self.errors.try_recv().map_or_else(|_| String::from("the serial writer stopped"), |e| e.to_string())
+                });
+                Err(std::io::Error::other(error.clone()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn framed_message_layout() {
+        let message = framed_message(&[1, 2, 250]).unwrap();
+        assert_eq!(message, [b'R', b'F', 0, 3, 1, 2, 250, 0]);
+        assert!(framed_message(&vec![0; usize::from(u16::MAX) + 1]).is_err());
+    }
+
+    #[test]
+    fn adalight_message_layout() {
+        // two LEDs, the second padded
+        let message = adalight_message(&[10, 20, 30, 40]).unwrap();
+        assert_eq!(message, [b'A', b'd', b'a', 0, 1, 0x54, 10, 20, 30, 40, 0, 0]);
+        assert!(adalight_message(&vec![0; (usize::from(u16::MAX) + 2) * 3]).is_err());
+    }
+
+    /// Pseudo terminal standing in for the microcontroller: the sink writes to
+    /// the returned device path, the test reads from the master.
+    #[cfg(target_os = "linux")]
+    fn open_pty() -> (File, String) {
+        use std::ffi::CStr;
+        use std::os::fd::FromRawFd;
+
+        // SAFETY: the master descriptor is checked before use and handed to the
+        // returned file, ptsname_r writes a nul terminated path into `name`.
+        unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(master >= 0, "could not open a pty: {}", std::io::Error::last_os_error());
+            assert_eq!(libc::grantpt(master), 0);
+            assert_eq!(libc::unlockpt(master), 0);
+            let mut name = [0 as libc::c_char; 128];
+            assert_eq!(libc::ptsname_r(master, name.as_mut_ptr(), name.len()), 0);
+            let path = CStr::from_ptr(name.as_ptr()).to_str().unwrap().to_string();
+            (File::from_raw_fd(master), path)
+        }
+    }
+
+    /// Reads `len` bytes from the pty master, failing after a few seconds.
+    #[cfg(target_os = "linux")]
+    fn read_pty(master: &mut File, len: usize) -> Vec<u8> {
+        use std::io::Read;
+        use std::os::fd::AsRawFd;
+
+        let mut data = vec![0u8; len];
+        let mut filled = 0;
+        while filled < len {
+            let mut poll = libc::pollfd { fd: master.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+            // SAFETY: `poll` is a single valid pollfd for the open master.
+            assert_eq!(unsafe { libc::poll(&mut poll, 1, 5000) }, 1, "timed out reading the pty");
+            filled += master.read(&mut data[filled..]).unwrap();
+        }
+        data
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn frames_reach_the_device() {
+        for adalight in [false, true] {
+            let (mut master, path) = open_pty();
+            let mut sink = SerialSink::new(&path, 115200, adalight).unwrap();
+            let data: Vec<u8> = (0..=255).collect();
+            sink.send(&data).unwrap();
+            let expected = if adalight { adalight_message(&data) } else { framed_message(&data) }.unwrap();
+            assert_eq!(read_pty(&mut master, expected.len()), expected);
+        }
+    }
+}
@@ -0,0 +1,465 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+use noise::{Fbm, MultiFractal, Perlin, Seedable};
+use crate::configs::{FireConfigs, SimulationMode};
+use crate::cooling_maps::{initialise_cooling_map, update_cooling_map};
+use crate::fire_handler::{advect, seed_fire, smooth_and_cool};
+use crate::fire_masks::{initialise_fire_base, initialise_fire_mask, update_fire_mask};
+use crate::fluid::FluidSolver;
+use crate::fuel::{burn_fuel, initialise_fuel};
+use crate::forces::update_wind;
+use crate::rng::Rng;
+use crate::snapshot::{SnapshotReader, SnapshotWriter};
+use crate::stats::{Stage, StageTimings};
+
+/// Complete state of a running fire. `buf` always holds the heat of the
+/// latest step, whichever simulation mode produced it.
+pub struct FireSimulation {
+    pub w: usize,
+    pub h: usize,
+    pub buf: Vec<u8>,
+    buf_new: Vec<u8>,
+    buf_smoothed: Vec<u8>,
+    wind: Vec<f64>,
+    fire_base: Vec<bool>,
+    pub fire_mask: Vec<u8>,
+    pub cooling_map: VecDeque<u8>,
+    pub obstacles: Vec<bool>,
+    pub fuel: Vec<u8>,
+    pub noise_function: Fbm<Perlin>,
+    mask_noise_function: Fbm<Perlin>,
+    gust_noise_function: Fbm<Perlin>,
+    pub ystart: f64,
+    mask_time: f64,
+    gust_time: f64,
+    fluid: Option<FluidSolver>,
+    /// Seed the simulation was started from, drawn at random if none is configured.
+    pub seed: u64,
+    pub rng: Rng,
+    /// Number of steps taken so far.
+    pub frame: u64,
+    /// Time spent in each stage, the caller resets it as it sees fit.
+    pub timings: StageTimings,
+}
+
+/// How long to run the simulation before the first frame is shown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PreRoll {
+    /// A fixed number of steps.
+    Frames(u64),
+    /// Until the mean heat, averaged over windows of `window` steps, changes
+    /// by less than `tolerance` relative to the previous window, but at most
+    /// `max_frames` steps.
+    Converge {
+        window: u64,
+        tolerance: f64,
+        max_frames: u64,
+    },
+}
+
+fn new_noise_function(seed: u32) -> Fbm<Perlin> {
+    Fbm::<Perlin>::new(seed).set_octaves(1)
+}
+
+impl FireSimulation {
+    pub fn new(fire_configs: &FireConfigs, w: usize, h: usize) -> Self {
+        // Seed rngs
+        let seed = fire_configs.seed.unwrap_or_else(Rng::random_seed);
+        let mut rng = Rng::new(seed);
+        let noise_function = new_noise_function(rng.next_u32());
+        let mask_noise_function = new_noise_function(rng.next_u32());
+        let gust_noise_function = new_noise_function(rng.next_u32());
+
+        // Prepare fire mask
+        let fire_base = initialise_fire_base(w, fire_configs.fill_percentage, &mut rng);
+        let fire_mask = initialise_fire_mask(&fire_base, 2);
+
+        let cooling_map = initialise_cooling_map(
+            w,
+            h,
+            &noise_function,
+            fire_configs.cooling_map_configs.length_scale,
+            fire_configs.cooling_map_configs.strength,
+        );
+
+        // Start fire
+        let obstacles = vec![false; w * h];
+        let mut buf = vec![0u8; w * h];
+        seed_fire(&mut buf, w, h, &fire_mask, &obstacles);
+
+        let mut simulation = Self {
+            w,
+            h,
+            buf,
+            buf_new: vec![0u8; w * h],
+            buf_smoothed: vec![0u8; w * h],
+            wind: vec![0f64; h],
+            fire_base,
+            fire_mask,
+            cooling_map,
+            obstacles,
+            fuel: vec![0u8; w * h],
+            noise_function,
+            mask_noise_function,
+            gust_noise_function,
+            ystart: 0.0,
+            mask_time: 0.0,
+            gust_time: 0.0,
+            fluid: None,
+            seed,
+            rng,
+            frame: 0,
+            timings: StageTimings::default(),
+        };
+        if fire_configs.fuel_configs.enabled {
+            simulation.refuel(fire_configs);
+        }
+        simulation
+    }
+
+    /// Lays out a fresh fuel bed and lights it.
+    pub fn refuel(&mut self, fire_configs: &FireConfigs) {
+        initialise_fuel(
+            &mut self.fuel,
+            &mut self.buf,
+            self.w,
+            self.h,
+            &fire_configs.fuel_configs,
+            &self.obstacles,
+            &mut self.rng,
+        );
+    }
+
+    /// Advances the simulation by one step.
+    pub fn step(&mut self, fire_configs: &FireConfigs) {
+        let start = Instant::now();
+        update_wind(
+            &mut self.wind,
+            &fire_configs.force_configs,
+            &self.gust_noise_function,
+            self.gust_time,
+        );
+        self.gust_time += fire_configs.force_configs.gust_speed;
+        self.timings.record(Stage::Forces, start);
+
+        let start = Instant::now();
+        update_fire_mask(
+            &mut self.fire_mask,
+            &mut self.fire_base,
+            &fire_configs.fire_mask_configs,
+            fire_configs.fill_percentage,
+            &self.mask_noise_function,
+            self.mask_time,
+            &mut self.rng,
+        );
+        self.mask_time += fire_configs.fire_mask_configs.speed;
+        self.timings.record(Stage::FireMask, start);
+
+        match fire_configs.simulation_mode {
+            SimulationMode::Classic => self.step_classic(fire_configs),
+            SimulationMode::Fluid => self.step_fluid(fire_configs),
+        }
+
+        // update cooling map buffer
+        let start = Instant::now();
+        update_cooling_map(
+            &mut self.cooling_map,
+            self.w,
+            self.h,
+            &self.noise_function,
+            fire_configs.cooling_map_configs.length_scale,
+            fire_configs.cooling_map_configs.strength,
+            self.ystart,
+        );
+        self.timings.record(Stage::CoolingMap, start);
+        self.ystart += fire_configs.cooling_map_configs.length_scale;
+        self.frame += 1;
+    }
+
+    /// Mean heat of the buffer, between 0 and 255.
+    pub fn mean_heat(&self) -> f64 {
+        self.buf.iter().map(|&v| u64::from(v)).sum::<u64>() as f64 / self.buf.len() as f64
+    }
+
+    /// Runs the simulation ahead without showing anything, so that the fire is
+    /// fully developed on the first frame. Returns the number of steps taken.
+    pub fn pre_roll(&mut self, fire_configs: &FireConfigs, pre_roll: PreRoll) -> u64 {
+        match pre_roll {
+            PreRoll::Frames(frames) => {
+                for _ in 0..frames {
+                    self.step(fire_configs);
+                }
+                frames
+            }
+            PreRoll::Converge { window, tolerance, max_frames } => {
+                let window = window.max(1);
+                let mut previous: Option<f64> = None;
+                let mut frames = 0;
+                while frames < max_frames {
+                    let mut sum = 0.0;
+                    for _ in 0..window {
+                        self.step(fire_configs);
+                        sum += self.mean_heat();
+                    }
+                    frames += window;
+                    let mean = sum / window as f64;
+                    if let Some(prev) = previous {
+                        if (mean - prev).abs() <= tolerance * prev.max(1.0) {
+                            break;
+                        }
+                    }
+                    previous = Some(mean);
+                }
+                frames
+            }
+        }
+    }
+
+    /// Serialises everything needed to continue the simulation exactly where
+    /// it is; scratch buffers and the wind are recomputed on every step.
+    pub fn write_state(&self, writer: &mut SnapshotWriter) {
+        writer.write_u64(self.w as u64);
+        writer.write_u64(self.h as u64);
+        writer.write_u64(self.seed);
+        writer.write_u64(self.rng.state());
+        writer.write_u64(self.frame);
+        writer.write_u32(self.noise_function.seed());
+        writer.write_u32(self.mask_noise_function.seed());
+        writer.write_u32(self.gust_noise_function.seed());
+        writer.write_f64(self.ystart);
+        writer.write_f64(self.mask_time);
+        writer.write_f64(self.gust_time);
+        writer.write_bytes(&self.buf);
+        writer.write_bools(&self.fire_base);
+        writer.write_bytes(&self.fire_mask);
+        writer.write_bytes(&self.cooling_map.iter().copied().collect::<Vec<u8>>());
+        writer.write_bools(&self.obstacles);
+        writer.write_bytes(&self.fuel);
+        match &self.fluid {
+            Some(fluid) => {
+                writer.write_u32(1);
+                fluid.write_state(writer);
+            }
+            None => writer.write_u32(0),
+        }
+    }
+
+    pub fn read_state(reader: &mut SnapshotReader) -> Result<Self, String> {
+        let w = reader.read_len()?;
+        let h = reader.read_len()?;
+        // the heat buffer alone takes a byte per cell
+        if w == 0 || h == 0 {
+            return Err(String::from("snapshot is corrupt"));
+        }
+        let size = reader.check_cells(w.checked_mul(h), 1)?;
+        let seed = reader.read_u64()?;
+        let rng = Rng::from_state(reader.read_u64()?);
+        let frame = reader.read_u64()?;
+        let noise_function = new_noise_function(reader.read_u32()?);
+        let mask_noise_function = new_noise_function(reader.read_u32()?);
+        let gust_noise_function = new_noise_function(reader.read_u32()?);
+        let ystart = reader.read_f64()?;
+        let mask_time = reader.read_f64()?;
+        let gust_time = reader.read_f64()?;
+        let buf = reader.read_bytes()?;
+        let fire_base = reader.read_bools()?;
+        let fire_mask = reader.read_bytes()?;
+        let cooling_map = VecDeque::from(reader.read_bytes()?);
+        let obstacles = reader.read_bools()?;
+        let fuel = reader.read_bytes()?;
+        let fluid = match reader.read_u32()? {
+            0 => None,
+            _ => Some(FluidSolver::read_state(reader)?),
+        };
+
+        if buf.len() != size
+            || fire_base.len() != w
+            || !(w..=size).contains(&fire_mask.len())
+            || fire_mask.len() % w != 0
+            || cooling_map.len() != size
+            || obstacles.len() != size
+            || fuel.len() != size
+        {
+            return Err(String::from("snapshot is corrupt"));
+        }
+
+        Ok(Self {
+            w,
+            h,
+            buf,
+            buf_new: vec![0u8; size],
+            buf_smoothed: vec![0u8; size],
+            wind: vec![0f64; h],
+            fire_base,
+            fire_mask,
+            cooling_map,
+            obstacles,
+            fuel,
+            noise_function,
+            mask_noise_function,
+            gust_noise_function,
+            ystart,
+            mask_time,
+            gust_time,
+            fluid,
+            seed,
+            rng,
+            frame,
+            timings: StageTimings::default(),
+        })
+    }
+
+    fn step_classic(&mut self, fire_configs: &FireConfigs) {
+        let (w, h) = (self.w, self.h);
+
+        // Perform smoothing and cooling
+        let start = Instant::now();
+        smooth_and_cool(
+            &self.buf,
+            &mut self.buf_smoothed,
+            w,
+            h,
+            0,
+            &self.cooling_map,
+            fire_configs.base_height,
+            &self.obstacles,
+        );
+        self.timings.record(Stage::Smoothing, start);
+
+        // Move the heat upwards and along the wind
+        let start = Instant::now();
+        advect(
+            &self.buf_smoothed,
+            &mut self.buf_new,
+            w,
+            h,
+            &self.wind,
+            fire_configs.force_configs.buoyancy,
+            &self.obstacles,
+        );
+        self.timings.record(Stage::Advection, start);
+
+        let start = Instant::now();
+        if fire_configs.fuel_configs.enabled {
+            burn_fuel(
+                &self.buf,
+                &mut self.buf_new,
+                &mut self.fuel,
+                w,
+                h,
+                &fire_configs.fuel_configs,
+                &mut self.rng,
+            );
+        } else {
+            seed_fire(&mut self.buf_new, w, h, &self.fire_mask, &self.obstacles);
+        }
+        self.timings.record(Stage::Seeding, start);
+
+        std::mem::swap(&mut self.buf, &mut self.buf_new);
+    }
+
+    fn step_fluid(&mut self, fire_configs: &FireConfigs) {
+        let start = Instant::now();
+        let (w, h) = (self.w, self.h);
+        let configs = &fire_configs.fluid_configs;
+        let scale = configs.resolution_scale.max(1);
+        let (nx, ny) = ((w / scale).max(1), (h / scale).max(1));
+
+        // (re)create the solver when it is first needed or the grid changed
+        let fluid = match &mut self.fluid {
+            Some(fluid) if fluid.nx == nx && fluid.ny == ny => fluid,
+            fluid => fluid.insert(FluidSolver::new(nx, ny)),
+        };
+
+        // the fire mask and wind are sampled at the fluid resolution
+        let source: Vec<f32> = (0..nx)
+            .map(|i| f32::from(self.fire_mask[i * scale]) / 255.0)
+            .collect();
+        fluid.inject(&source, configs);
+        let force: Vec<f32> = (0..ny)
+            .map(|j| self.wind[j * scale] as f32 / scale as f32)
+            .collect();
+        fluid.add_horizontal_force(&force, configs.time_step);
+        // the cooling map breaks the rising sheet of heat up into flames
+        let cooling: Vec<f32> = (0..nx * ny)
+            .map(|i| {
+                let (x, y) = ((i % nx) * scale, (i / nx) * scale);
+                f32::from(self.cooling_map[x + y * w]) / 255.0 * configs.cooling_map_coupling
+            })
+            .collect();
+        fluid.cool(&cooling, configs.time_step);
+        let solid: Vec<bool> = (0..nx * ny)
+            .map(|i| self.obstacles[(i % nx) * scale + (i / nx) * scale * w])
+            .collect();
+        fluid.step(configs);
+        fluid.clear_solid(&solid);
+
+        // upsample the temperature into the heat buffer
+        for y in 0..h {
+            let fy = ((y as f32 + 0.5) / scale as f32 + 0.5).clamp(1.0, ny as f32);
+            let j0 = fy.floor() as usize;
+            let j1 = (j0 + 1).min(ny);
+            let ty = fy - j0 as f32;
+            for x in 0..w {
+                let fx = ((x as f32 + 0.5) / scale as f32 + 0.5).clamp(1.0, nx as f32);
+                let i0 = fx.floor() as usize;
+                let i1 = (i0 + 1).min(nx);
+                let tx = fx - i0 as f32;
+                let t = &fluid.temperature;
+                let top = t[fluid.idx(i0, j0)] * (1.0 - tx) + t[fluid.idx(i1, j0)] * tx;
+                let bottom = t[fluid.idx(i0, j1)] * (1.0 - tx) + t[fluid.idx(i1, j1)] * tx;
+                let val = top * (1.0 - ty) + bottom * ty;
+                self.buf[x + y * w] = if self.obstacles[x + y * w] {
+                    0
+                } else {
+                    (val.clamp(0.0, 1.0) * 255.0).round() as u8
+                };
+            }
+        }
+        self.timings.record(Stage::Fluid, start);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configs::FireMaskMode;
+
+    const GOLDEN_PATH: &str = "tests/data/classic-48x32-seed42.bin";
+    const GOLDEN_STEPS: u64 = 200;
+
+    /// A small classic run that also draws from the generator every step
+    /// through the flickering mask and the gusts.
+    fn golden_run() -> FireSimulation {
+        let mut fire_configs = FireConfigs::default();
+        fire_configs.seed = Some(42);
+        fire_configs.fire_mask_configs.mode = FireMaskMode::Flicker;
+        fire_configs.force_configs.wind = 0.3;
+        fire_configs.force_configs.gust_strength = 0.5;
+        let mut simulation = FireSimulation::new(&fire_configs, 48, 32);
+        for _ in 0..GOLDEN_STEPS {
+            simulation.step(&fire_configs);
+        }
+        simulation
+    }
+
+    #[test]
+    fn same_seed_gives_identical_frames() {
+        let mut first = golden_run();
+        let mut second = golden_run();
+        assert_eq!(first.buf, second.buf);
+        assert_eq!(first.rng.next_u32(), second.rng.next_u32());
+    }
+
+    /// Set `RUSTY_FIRE_UPDATE_GOLDEN=1` to rewrite the golden buffer after an
+    /// intended change of the simulation.
+    #[test]
+    fn matches_golden_buffer() {
+        let simulation = golden_run();
+        if std::env::var_os("RUSTY_FIRE_UPDATE_GOLDEN").is_some() {
+            std::fs::write(GOLDEN_PATH, &simulation.buf).unwrap();
+        }
+        let golden = std::fs::read(GOLDEN_PATH).unwrap();
+        assert!(simulation.buf == golden, "the frame after {} steps differs from {}", GOLDEN_STEPS, GOLDEN_PATH);
+    }
+}
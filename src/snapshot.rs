@@ -0,0 +1,233 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use crate::configs::FireConfigs;
+use crate::simulation::FireSimulation;
+
+const MAGIC: &[u8; 8] = b"RFIRESNP";
+const VERSION: u32 = 1;
+/// Largest grid a snapshot may describe, far beyond any screen.
+pub const MAX_CELLS: usize = 1 << 28;
+
+/// Little endian writer for the snapshot payload.
+pub struct SnapshotWriter {
+    data: Vec<u8>,
+}
+
+impl SnapshotWriter {
+    pub fn new() -> Self {
+        Self { data: Vec::new() }
+    }
+
+    pub fn write_u32(&mut self, val: u32) {
+        self.data.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, val: u64) {
+        self.data.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn write_f64(&mut self, val: f64) {
+        self.data.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn write_bytes(&mut self, val: &[u8]) {
+        self.write_u64(val.len() as u64);
+        self.data.extend_from_slice(val);
+    }
+
+    pub fn write_str(&mut self, val: &str) {
+        self.write_bytes(val.as_bytes());
+    }
+
+    /// Packs the flags into bits, eight per byte.
+    pub fn write_bools(&mut self, val: &[bool]) {
+        self.write_u64(val.len() as u64);
+        for chunk in val.chunks(8) {
+            let byte = chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (i, &bit)| byte | (u8::from(bit) << i));
+            self.data.push(byte);
+        }
+    }
+
+    pub fn write_f32s(&mut self, val: &[f32]) {
+        self.write_u64(val.len() as u64);
+        for v in val {
+            self.data.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+}
+
+/// Counterpart of [`SnapshotWriter`].
+pub struct SnapshotReader {
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl SnapshotReader {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        if self.data.len() - self.pos < n {
+            return Err(String::from("snapshot is truncated"));
+        }
+        let slice = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(slice)
+    }
+
+    pub fn read_len(&mut self) -> Result<usize, String> {
+        usize::try_from(self.read_u64()?).map_err(|_| String::from("snapshot is corrupt"))
+    }
+
+    /// Checks the number of cells of a grid read from the snapshot before
+    /// anything is allocated for it: it must not overflow, stay below a sane
+    /// limit and the rest of the payload must hold `bytes_per_cell` bytes for
+    /// each cell.
+    pub fn check_cells(&self, cells: Option<usize>, bytes_per_cell: usize) -> Result<usize, String> {
+        match cells {
+            Some(cells) if cells <= MAX_CELLS && cells * bytes_per_cell <= self.data.len() - self.pos => Ok(cells),
+            _ => Err(String::from("snapshot is corrupt")),
+        }
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn read_f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn read_bytes(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.read_len()?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    pub fn read_str(&mut self) -> Result<String, String> {
+        String::from_utf8(self.read_bytes()?).map_err(|_| String::from("snapshot is corrupt"))
+    }
+
+    pub fn read_bools(&mut self) -> Result<Vec<bool>, String> {
+        let len = self.read_len()?;
+        let bytes = self.take(len.div_ceil(8))?;
+        Ok((0..len).map(|i| bytes[i / 8] & (1 << (i % 8)) != 0).collect())
+    }
+
+    pub fn read_f32s(&mut self) -> Result<Vec<f32>, String> {
+        let len = self.read_len()?;
+        let bytes = self.take(len.checked_mul(4).ok_or("snapshot is corrupt")?)?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect())
+    }
+}
+
+/// Saves the complete state of the simulation together with the configs that
+/// drive it. The payload is zlib compressed.
+pub fn save_snapshot(
+    path: &str,
+    simulation: &FireSimulation,
+    fire_configs: &FireConfigs,
+) -> Result<(), String> {
+    let mut writer = SnapshotWriter::new();
+    writer.write_str(&fire_configs.to_text());
+    simulation.write_state(&mut writer);
+
+    let error = |e: std::io::Error| format!("could not write snapshot {}: {}", path, e);
+    let mut file = BufWriter::new(File::create(path).map_err(error)?);
+    file.write_all(MAGIC).map_err(error)?;
+    file.write_all(&VERSION.to_le_bytes()).map_err(error)?;
+    let mut encoder = ZlibEncoder::new(file, Compression::default());
+    encoder.write_all(&writer.data).map_err(error)?;
+    encoder.finish().map_err(error)?.flush().map_err(error)?;
+    Ok(())
+}
+
+/// Loads a snapshot written by [`save_snapshot`], returning the restored
+/// simulation and its configs.
+pub fn load_snapshot(path: &str) -> Result<(FireSimulation, FireConfigs), String> {
+    let error = |e: std::io::Error| format!("could not read snapshot {}: {}", path, e);
+    let mut file = BufReader::new(File::open(path).map_err(error)?);
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header).map_err(error)?;
+    if &header[..8] != MAGIC {
+        return Err(format!("{} is not a snapshot", path));
+    }
+    let version = u32::from_le_bytes(header[8..].try_into().unwrap());
+    if version != VERSION {
+        return Err(format!("unsupported snapshot version {}", version));
+    }
+    let mut data = Vec::new();
+    ZlibDecoder::new(file).read_to_end(&mut data).map_err(error)?;
+
+    let mut reader = SnapshotReader::new(data);
+    let mut fire_configs = FireConfigs::default();
+    fire_configs.apply_text(&reader.read_str()?)?;
+    let simulation = FireSimulation::read_state(&mut reader)?;
+    Ok((simulation, fire_configs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fluid::FluidSolver;
+
+    #[test]
+    fn round_trips_the_simulation() {
+        let mut fire_configs = FireConfigs::default();
+        fire_configs.seed = Some(7);
+        let mut simulation = FireSimulation::new(&fire_configs, 32, 24);
+        simulation.step(&fire_configs);
+        let mut writer = SnapshotWriter::new();
+        simulation.write_state(&mut writer);
+        let restored = FireSimulation::read_state(&mut SnapshotReader::new(writer.data)).unwrap();
+        assert_eq!((restored.w, restored.h, restored.frame), (32, 24, 1));
+        assert_eq!(restored.buf, simulation.buf);
+    }
+
+    #[test]
+    fn rejects_oversized_grids() {
+        let fire_configs = FireConfigs::default();
+        let mut writer = SnapshotWriter::new();
+        FireSimulation::new(&fire_configs, 16, 12).write_state(&mut writer);
+        for (w, h) in [(u64::MAX, 2u64), (1 << 32, 1 << 32), (1 << 20, 1 << 20), (0, 12)] {
+            let mut data = writer.data.clone();
+            data[..8].copy_from_slice(&w.to_le_bytes());
+            data[8..16].copy_from_slice(&h.to_le_bytes());
+            assert!(FireSimulation::read_state(&mut SnapshotReader::new(data)).is_err());
+        }
+
+        for (nx, ny) in [(u64::MAX, 0), (1 << 40, 1 << 40), (1000, 1000)] {
+            let mut writer = SnapshotWriter::new();
+            writer.write_u64(nx);
+            writer.write_u64(ny);
+            writer.write_f32s(&[0.0; 16]);
+            assert!(FluidSolver::read_state(&mut SnapshotReader::new(writer.data)).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_fire_masks_without_whole_rows() {
+        let fire_configs = FireConfigs::default();
+        let mut simulation = FireSimulation::new(&fire_configs, 16, 12);
+        for (length, valid) in [(0, false), (8, false), (16, true), (16 * 12, true), (16 * 13, false)] {
+            simulation.fire_mask = vec![0; length];
+            let mut writer = SnapshotWriter::new();
+            simulation.write_state(&mut writer);
+            let restored = FireSimulation::read_state(&mut SnapshotReader::new(writer.data));
+            assert_eq!(restored.is_ok(), valid, "fire mask of {} cells", length);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+/// Stages of a frame that are timed separately.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Forces,
+    FireMask,
+    Smoothing,
+    Advection,
+    Seeding,
+    Fluid,
+    CoolingMap,
+    ColorMap,
+    Upload,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 9] = [
+        Stage::Forces,
+        Stage::FireMask,
+        Stage::Smoothing,
+        Stage::Advection,
+        Stage::Seeding,
+        Stage::Fluid,
+        Stage::CoolingMap,
+        Stage::ColorMap,
+        Stage::Upload,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Forces => "forces",
+            Stage::FireMask => "fire_mask",
+            Stage::Smoothing => "smoothing",
+            Stage::Advection => "advection",
+            Stage::Seeding => "seeding",
+            Stage::Fluid => "fluid",
+            Stage::CoolingMap => "cooling_map",
+            Stage::ColorMap => "color_map",
+            Stage::Upload => "upload",
+        }
+    }
+}
+
+/// Time spent in each stage, accumulated over all steps since the last reset.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StageTimings {
+    durations: [Duration; Stage::ALL.len()],
+}
+
+impl StageTimings {
+    /// Adds the time elapsed since `start` to `stage`.
+    pub fn record(&mut self, stage: Stage, start: Instant) {
+        self.durations[stage as usize] += start.elapsed();
+    }
+
+    pub fn get(&self, stage: Stage) -> Duration {
+        self.durations[stage as usize]
+    }
+
+    pub fn total(&self) -> Duration {
+        self.durations.iter().sum()
+    }
+}
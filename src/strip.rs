@@ -0,0 +1,85 @@
+use crate::configs::{FireConfigs, StripLayout};
+use crate::rng::Rng;
+
+/// One dimensional fire along a strip of LEDs. Sparks are injected at the
+/// base, the heat drifts away from it while diffusing and cools randomly.
+pub struct StripSimulation {
+    /// Heat along the fire, starting at its base. Mirrored layouts show it
+    /// twice, so it is only half as long as the strip then.
+    pub heat: Vec<u8>,
+    pub seed: u64,
+    pub rng: Rng,
+    /// Number of steps taken so far.
+    pub frame: u64,
+}
+
+/// Number of cells the fire needs to fill `length` LEDs in `layout`.
+fn fire_length(length: usize, layout: StripLayout) -> usize {
+    match layout {
+        StripLayout::Normal => length,
+        StripLayout::Mirrored | StripLayout::CentreOut => length.div_ceil(2),
+    }
+}
+
+impl StripSimulation {
+    pub fn new(fire_configs: &FireConfigs) -> Self {
+        let seed = fire_configs.seed.unwrap_or_else(Rng::random_seed);
+        let configs = &fire_configs.strip_configs;
+        Self {
+            heat: vec![0u8; fire_length(configs.length, configs.layout)],
+            seed,
+            rng: Rng::new(seed),
+            frame: 0,
+        }
+    }
+
+    /// Advances the fire by one step. Changes of the length or the layout
+    /// take effect immediately, keeping the heat at the base.
+    pub fn step(&mut self, fire_configs: &FireConfigs) {
+        let configs = &fire_configs.strip_configs;
+        let n = fire_length(configs.length, configs.layout);
+        self.heat.resize(n, 0);
+
+        // cool down every cell a little
+        for cell in self.heat.iter_mut() {
+            let cooling = self.rng.gen_range(0, configs.cooling.saturating_add(1));
+            *cell = cell.saturating_sub(cooling);
+        }
+
+        // heat drifts away from the base and diffuses a little
+        for i in (2..n).rev() {
+            let drifted = (u16::from(self.heat[i - 1]) + 2 * u16::from(self.heat[i - 2])) / 3;
+            self.heat[i] = drifted as u8;
+        }
+
+        // randomly ignite new sparks near the base
+        if self.rng.gen_f64() < configs.sparking {
+            let zone = configs.spark_zone.clamp(1, n.min(255)) as u8;
+            let i = usize::from(self.rng.gen_range(0, zone));
+            let spark = self.rng.gen_range(configs.spark_min, configs.spark_max);
+            self.heat[i] = self.heat[i].saturating_add(spark);
+        }
+        self.frame += 1;
+    }
+
+    /// Heat of every LED of the strip, in wiring order.
+    pub fn leds(&self, fire_configs: &FireConfigs) -> Vec<u8> {
+        let configs = &fire_configs.strip_configs;
+        let length = configs.length;
+        let last = self.heat.len().saturating_sub(1);
+        let mut leds: Vec<u8> = (0..length)
+            .map(|i| {
+                let cell = match configs.layout {
+                    StripLayout::Normal => i,
+                    StripLayout::Mirrored => i.min(length - 1 - i),
+                    StripLayout::CentreOut => (2 * i).abs_diff(length - 1) / 2,
+                };
+                self.heat.get(cell.min(last)).copied().unwrap_or(0)
+            })
+            .collect();
+        if configs.reversed {
+            leds.reverse();
+        }
+        leds
+    }
+}
@@ -0,0 +1,390 @@
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use crate::audio::AudioReactive;
+use crate::color_maps::ColorLut;
+use crate::configs::FireConfigs;
+use crate::control::{Command, Controls};
+use crate::defaults::{DEFAULT_WINDOW_HEIGHT, DEFAULT_WINDOW_WIDTH};
+use crate::graphics::{encode_kitty, encode_sixel};
+use crate::http::LiveStatus;
+use crate::led_output::LedOutput;
+use crate::obstacles::load_obstacle_mask;
+use crate::simulation::FireSimulation;
+use crate::snapshot::save_snapshot;
+use crate::timing::{interpolate, FixedTimestep, Playback};
+
+/// Characters of increasing brightness for terminals without colors.
+const ASCII_RAMP: &[u8] = b" .:-=+*%@";
+/// Size assumed if the terminal does not report one.
+const FALLBACK_SIZE: (usize, usize) = (80, 24);
+
+/// Set by the interrupt handler so the terminal is restored before exiting.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// How the terminal renderer draws the heat.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerminalMode {
+    /// Upper half blocks with 24-bit colors, two pixels per character cell.
+    TrueColor,
+    /// Upper half blocks with the 256 color palette.
+    Ansi256,
+    /// One character of a brightness ramp per pixel.
+    Ascii,
+    /// Full resolution Sixel images.
+    Sixel,
+    /// Full resolution images with the Kitty graphics protocol.
+    Kitty,
+}
+
+impl TerminalMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            TerminalMode::TrueColor => "truecolor",
+            TerminalMode::Ansi256 => "256",
+            TerminalMode::Ascii => "ascii",
+            TerminalMode::Sixel => "sixel",
+            TerminalMode::Kitty => "kitty",
+        }
+    }
+
+    /// Parses a mode name, `auto` detects the best mode of the terminal.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "auto" => Some(Self::detect()),
+            "truecolor" => Some(TerminalMode::TrueColor),
+            "256" => Some(TerminalMode::Ansi256),
+            "ascii" => Some(TerminalMode::Ascii),
+            "sixel" => Some(TerminalMode::Sixel),
+            "kitty" => Some(TerminalMode::Kitty),
+            _ => None,
+        }
+    }
+
+    /// Guesses the color support of the terminal from the environment. Sixel
+    /// support cannot be told from it and has to be asked for explicitly.
+    pub fn detect() -> Self {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        if term.contains("kitty") || std::env::var_os("KITTY_WINDOW_ID").is_some() {
+            TerminalMode::Kitty
+        } else if colorterm == "truecolor" || colorterm == "24bit" {
+            TerminalMode::TrueColor
+        } else if term.contains("256color") {
+            TerminalMode::Ansi256
+        } else {
+            TerminalMode::Ascii
+        }
+    }
+
+}
+
+/// Size of the terminal attached to stdout.
+struct TerminalSize {
+    columns: usize,
+    rows: usize,
+    /// Size in pixels, zero if the terminal does not report it.
+    width: usize,
+    height: usize,
+}
+
+#[cfg(unix)]
+fn terminal_size() -> Option<TerminalSize> {
+    // SAFETY: TIOCGWINSZ only writes into the winsize struct passed to it.
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_col > 0 && size.ws_row > 0).then(|| TerminalSize {
+        columns: usize::from(size.ws_col),
+        rows: usize::from(size.ws_row),
+        width: usize::from(size.ws_xpixel),
+        height: usize::from(size.ws_ypixel),
+    })
+}
+
+#[cfg(not(unix))]
+fn terminal_size() -> Option<TerminalSize> {
+    None
+}
+
+/// Size of the grid that fills the terminal, leaving the last row for the
+/// status line. Images fill it at full resolution if the terminal reports its
+/// size in pixels, otherwise they have the size of the window.
+pub fn terminal_grid_size(mode: TerminalMode) -> (usize, usize) {
+    let size = terminal_size().unwrap_or(TerminalSize {
+        columns: FALLBACK_SIZE.0,
+        rows: FALLBACK_SIZE.1,
+        width: 0,
+        height: 0,
+    });
+    let rows = size.rows.saturating_sub(1).max(1);
+    match mode {
+        TerminalMode::TrueColor | TerminalMode::Ansi256 => (size.columns, rows * 2),
+        TerminalMode::Ascii => (size.columns, rows),
+        TerminalMode::Sixel | TerminalMode::Kitty => {
+            let (w, h) = if size.width > 0 && size.height > 0 {
+                (size.width, size.height * rows / size.rows)
+            } else {
+                (DEFAULT_WINDOW_WIDTH as usize, DEFAULT_WINDOW_HEIGHT as usize)
+            };
+            // Sixel images are drawn in bands of six rows
+            let h = if mode == TerminalMode::Sixel { h / 6 * 6 } else { h };
+            (w.max(1), h.max(6))
+        }
+    }
+}
+
+/// Handles Ctrl-C and SIGTERM by setting a flag that [`interrupted`]
+/// reports, so loops without a window can clean up before exiting.
+#[cfg(unix)]
+pub fn catch_interrupt() {
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+    }
+    // SAFETY: the handler only stores to an atomic, which is async signal safe.
+    unsafe {
+        libc::signal(libc::SIGINT, on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn catch_interrupt() {}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Index of the closest color in the xterm 256 color palette, either from
+/// the 6x6x6 color cube or from the gray ramp.
+fn ansi256_index([r, g, b, _]: [u8; 4]) -> u8 {
+    let cube_level = |c: u8| if c < 48 { 0 } else if c < 115 { 1 } else { (c - 35) / 40 };
+    let cube_value = |level: u8| if level == 0 { 0 } else { 55 + 40 * level };
+    let (lr, lg, lb) = (cube_level(r), cube_level(g), cube_level(b));
+    let cube = [cube_value(lr), cube_value(lg), cube_value(lb)];
+
+    let mean = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+    let gray_level = (mean.saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + 10 * gray_level;
+
+    let distance = |[cr, cg, cb]: [u8; 3]| {
+        [(r, cr), (g, cg), (b, cb)]
+            .iter()
+            .map(|&(a, b)| (i32::from(a) - i32::from(b)).pow(2))
+            .sum::<i32>()
+    };
+    if distance([gray, gray, gray]) < distance(cube) {
+        232 + gray_level
+    } else {
+        16 + 36 * lr + 6 * lg + lb
+    }
+}
+
+fn write_color(out: &mut Vec<u8>, mode: TerminalMode, layer: u8, color: [u8; 4]) {
+    match mode {
+        TerminalMode::TrueColor => {
+            let _ = write!(out, "\x1b[{}8;2;{};{};{}m", layer, color[0], color[1], color[2]);
+        }
+        TerminalMode::Ansi256 => {
+            let _ = write!(out, "\x1b[{}8;5;{}m", layer, ansi256_index(color));
+        }
+        TerminalMode::Ascii | TerminalMode::Sixel | TerminalMode::Kitty => {}
+    }
+}
+
+/// Renders a frame into `out` as escape sequences and characters or as an
+/// image, starting at the top left corner of the terminal. Colors of
+/// characters are only emitted where they change.
+#[allow(clippy::too_many_arguments)]
+pub fn render_frame(
+    out: &mut Vec<u8>,
+    heat: &[u8],
+    obstacles: &[bool],
+    w: usize,
+    h: usize,
+    color_lut: &ColorLut,
+    obstacle_color: [u8; 4],
+    mode: TerminalMode,
+) {
+    out.extend_from_slice(b"\x1b[H");
+    match mode {
+        TerminalMode::Sixel => {
+            return encode_sixel(out, heat, obstacles, w, h, color_lut, obstacle_color);
+        }
+        TerminalMode::Kitty => {
+            return encode_kitty(out, heat, obstacles, w, h, color_lut, obstacle_color);
+        }
+        _ => {}
+    }
+    let color = |i: usize| if obstacles[i] { obstacle_color } else { color_lut.color(heat[i]) };
+    let rows = if mode == TerminalMode::Ascii { h } else { h / 2 };
+    for row in 0..rows {
+        match mode {
+            TerminalMode::TrueColor | TerminalMode::Ansi256 => {
+                let mut current = None;
+                for x in 0..w {
+                    let top = color(x + 2 * row * w);
+                    let bottom = color(x + (2 * row + 1) * w);
+                    if current != Some((top, bottom)) {
+                        write_color(out, mode, 3, top);
+                        write_color(out, mode, 4, bottom);
+                        current = Some((top, bottom));
+                    }
+                    out.extend_from_slice("▀".as_bytes());
+                }
+                out.extend_from_slice(b"\x1b[0m");
+            }
+            TerminalMode::Ascii => {
+                out.extend(heat[row * w..(row + 1) * w].iter().zip(&obstacles[row * w..]).map(
+                    |(&val, &solid)| if solid {
+                        b'#'
+                    } else {
+                        ASCII_RAMP[usize::from(val) * ASCII_RAMP.len() / 256]
+                    },
+                ));
+            }
+            TerminalMode::Sixel | TerminalMode::Kitty => unreachable!(),
+        }
+        out.extend_from_slice(b"\r\n");
+    }
+}
+
+/// Creates a simulation for a grid of the given size, with the obstacle mask
+/// stretched over it.
+fn resized_simulation(fire_configs: &FireConfigs, w: usize, h: usize) -> Result<FireSimulation, String> {
+    let mut simulation = FireSimulation::new(fire_configs, w, h);
+    if let Some(path) = &fire_configs.obstacle_configs.mask_path {
+        simulation.obstacles = load_obstacle_mask(path, w, h)?;
+    }
+    Ok(simulation)
+}
+
+/// Runs the simulation in the terminal until interrupted, drawing at most
+/// `fps` frames per second. When the terminal is resized, the simulation
+/// restarts on a grid of the new size.
+pub fn run_terminal(
+    mut simulation: FireSimulation,
+    mut fire_configs: FireConfigs,
+    controls: &Controls,
+    mut audio: Option<AudioReactive>,
+    mode: TerminalMode,
+    fps: f64,
+) -> Result<(), String> {
+    catch_interrupt();
+    let mut color_lut = ColorLut::named(&fire_configs.color_map_name);
+    let [r, g, b] = fire_configs.obstacle_configs.color;
+    let obstacle_color = [r, g, b, 255];
+    let frame_duration = Duration::from_secs_f64(1.0 / fps);
+    let mut timestep = FixedTimestep::new(
+        fire_configs.timing_configs.simulation_rate,
+        fire_configs.timing_configs.max_steps_per_frame,
+    );
+    let mut previous = simulation.buf.clone();
+    let mut interpolated = simulation.buf.clone();
+    let mut out = Vec::new();
+    let mut led_output = LedOutput::new();
+    let mut pixels = Vec::new();
+    let mut playback = Playback::new();
+    let mut status = String::new();
+    let mut stdout = std::io::stdout().lock();
+    let error = |e: std::io::Error| format!("could not write to the terminal: {}", e);
+
+    // switch to the alternate screen and hide the cursor
+    stdout.write_all(b"\x1b[?1049h\x1b[?25l\x1b[2J").map_err(error)?;
+    let mut last_frame = Instant::now();
+    let mut result = Ok(());
+    while !interrupted() {
+        if controls.apply(&mut fire_configs) {
+            color_lut = ColorLut::named(&fire_configs.color_map_name);
+        }
+        for command in controls.commands() {
+            match command {
+                Command::Pause => playback.paused = true,
+                Command::Resume => playback.paused = false,
+                // reported in the status line, as printing would garble the screen
+                Command::SaveSnapshot(path) => {
+                    status = match save_snapshot(&path, &simulation, &fire_configs) {
+                        Ok(()) => format!("saved snapshot to {}", path),
+                        Err(e) => e,
+                    };
+                }
+            }
+        }
+        let (w, h) = terminal_grid_size(mode);
+        if (w, h) != (simulation.w, simulation.h) {
+            simulation = match resized_simulation(&fire_configs, w, h) {
+                Ok(simulation) => simulation,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            };
+            previous = simulation.buf.clone();
+            interpolated = simulation.buf.clone();
+            out.extend_from_slice(b"\x1b[2J");
+        }
+
+        let frame_time = last_frame.elapsed();
+        last_frame = Instant::now();
+        let steps = if playback.paused { 0 } else { timestep.advance(frame_time.as_secs_f64()) };
+        for _ in 0..steps {
+            if let Some(audio) = &mut audio {
+                audio.step(&mut fire_configs);
+            }
+            previous.copy_from_slice(&simulation.buf);
+            simulation.step(&fire_configs);
+        }
+        let heat = if fire_configs.timing_configs.interpolate {
+            interpolate(&previous, &simulation.buf, timestep.alpha(), &mut interpolated);
+            &interpolated
+        } else {
+            &simulation.buf
+        };
+
+        render_frame(&mut out, heat, &simulation.obstacles, w, h, &color_lut, obstacle_color, mode);
+        let send_leds = led_output.is_due(&fire_configs.output_configs);
+        let stream = controls.wants_frame();
+        if send_leds || stream {
+            pixels.resize(w * h, [0; 4]);
+            color_lut.apply_masked(heat, &simulation.obstacles, obstacle_color, &mut pixels);
+        }
+        if send_leds {
+            led_output.send_frame(&fire_configs.output_configs, &pixels, w, h);
+        }
+        if stream {
+            controls.publish_frame(&pixels, w, h);
+        }
+        controls.publish(
+            &fire_configs,
+            LiveStatus { paused: playback.paused, seed: simulation.seed, frame: simulation.frame },
+        );
+        // the status line goes into the last row, below images too
+        let rows = terminal_size().map_or(FALLBACK_SIZE.1, |size| size.rows);
+        let _ = write!(
+            out,
+            "\x1b[{};1H\x1b[2KSeed: {}  Frame: {}  Mode: {}{}  {}",
+            rows,
+            simulation.seed,
+            simulation.frame,
+            mode.name(),
+            if playback.paused { "  Paused" } else { "" },
+            status,
+        );
+        if let Err(e) = stdout.write_all(&out).and_then(|()| stdout.flush()) {
+            result = Err(error(e));
+            break;
+        }
+        out.clear();
+
+        if let Some(remaining) = frame_duration.checked_sub(last_frame.elapsed()) {
+            std::thread::sleep(remaining);
+        }
+    }
+
+    // remove images, restore the cursor and the main screen
+    if mode == TerminalMode::Kitty {
+        stdout.write_all(b"\x1b_Ga=d,q=2\x1b\\").map_err(error)?;
+    }
+    stdout.write_all(b"\x1b[0m\x1b[?25h\x1b[?1049l").map_err(error)?;
+    stdout.flush().map_err(error)?;
+    result
+}
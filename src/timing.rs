@@ -0,0 +1,81 @@
+const MIN_SPEED: f64 = 0.25;
+const MAX_SPEED: f64 = 8.0;
+
+/// Fixed timestep accumulator. Render frames add their duration and the
+/// simulation takes as many fixed steps as fit in the accumulated time, so
+/// the fire runs at the same speed regardless of the display refresh rate.
+pub struct FixedTimestep {
+    step_duration: f64,
+    accumulator: f64,
+    max_steps: u32,
+}
+
+impl FixedTimestep {
+    pub fn new(rate: f64, max_steps: u32) -> Self {
+        Self {
+            step_duration: 1.0 / rate,
+            accumulator: 0.0,
+            max_steps,
+        }
+    }
+
+    pub fn configure(&mut self, rate: f64, max_steps: u32) {
+        self.step_duration = 1.0 / rate;
+        self.max_steps = max_steps;
+    }
+
+    /// Adds `frame_time` seconds and returns the number of steps to take.
+    /// At most `max_steps` are returned, any time beyond that is dropped so a
+    /// slow machine falls behind instead of spiralling into ever more steps.
+    pub fn advance(&mut self, frame_time: f64) -> u32 {
+        self.accumulator += frame_time;
+        let steps = (self.accumulator / self.step_duration).floor();
+        if steps > f64::from(self.max_steps) {
+            self.accumulator = 0.0;
+            return self.max_steps;
+        }
+        self.accumulator -= steps * self.step_duration;
+        steps as u32
+    }
+
+    /// How far the render time is between the last two steps, in `[0, 1)`.
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / self.step_duration).clamp(0.0, 1.0)
+    }
+}
+
+/// Pause and speed controls layered on top of the fixed timestep.
+pub struct Playback {
+    pub paused: bool,
+    /// Multiplier of the simulation rate, a power of two.
+    pub speed: f64,
+}
+
+impl Playback {
+    pub fn new() -> Self {
+        Self {
+            paused: false,
+            speed: 1.0,
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed * 2.0).min(MAX_SPEED);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = (self.speed / 2.0).max(MIN_SPEED);
+    }
+}
+
+/// Blends the heat of the previous and the current step by `alpha`.
+pub fn interpolate(previous: &[u8], current: &[u8], alpha: f64, out: &mut [u8]) {
+    let a = (alpha.clamp(0.0, 1.0) * 256.0) as u16;
+    for ((o, &p), &c) in out.iter_mut().zip(previous).zip(current) {
+        *o = ((u16::from(p) * (256 - a) + u16::from(c) * a) >> 8) as u8;
+    }
+}